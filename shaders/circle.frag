@@ -1,14 +1,15 @@
-uniform vec2 u_res;
-uniform vec2 u_mouse;
-uniform float u_time;
+#version 330 core
 
-float circle(in vec2 _st, in float _radius) {
-	vec2 dist = _st - vec2(0.5);
-	return 1. - smoothstep(_radius - (_radius * 0.01), _radius + (_radius * 0.01), dot(dist, dist) * 4.0);
-}
+in vec2 local;
+in float radius;
+in vec4 color;
+
+out vec4 FragColor;
 
 void main() {
-	vec2 st = gl_FragCoord.xy / u_res.xy;
-	vec3 color = vec3(circle(st, 0.9));
-	gl_FragColor = vec4(color, 1.0);
+	// pixels from the centre, the edge fades over one pixel
+	float coverage = clamp(radius - length(local) + 0.5, 0.0, 1.0);
+	// premultiplied, so the same output works for blending over, adding and taking the maximum
+	float alpha = color.a * coverage;
+	FragColor = vec4(color.rgb * alpha, alpha);
 }
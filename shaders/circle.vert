@@ -1,6 +1,24 @@
-layout (location = 0) in vec2 aPos;
-layout (location = 1) in vec2 aTexCoords;
+#version 330 core
+
+layout (location = 0) in vec2 aCorner;
+layout (location = 1) in vec3 aCircle;
+layout (location = 2) in vec4 aColor;
+
+// simulation size, and the monitor in clip space as left, bottom, right, top
+uniform vec2 u_size;
+uniform vec4 u_rect;
+// monitor pixels per simulation unit
+uniform float u_scale;
+
+out vec2 local;
+out float radius;
+out vec4 color;
 
 void main() {
-	gl_Position = vec4(aPos, 0.0, 1.0);
+	// a pixel of margin around the circle for the smoothed edge
+	radius = aCircle.z * u_scale;
+	local = aCorner * (radius + 1.0);
+	vec2 pos = (aCircle.xy + local / u_scale) / u_size;
+	gl_Position = vec4(mix(u_rect.xy, u_rect.zw, pos), 0.0, 1.0);
+	color = aColor;
 }
@@ -0,0 +1,23 @@
+#version 330 core
+
+out vec4 FragColor;
+
+in vec2 texCoords;
+
+uniform sampler2D tex;
+// the bright parts, blurred horizontally by post_bloom_blur
+uniform sampler2D u_bloom;
+uniform float u_radius;
+uniform float u_intensity;
+
+void main() {
+	vec2 step = vec2(0.0, u_radius / 8.0 / float(textureSize(u_bloom, 0).y));
+	vec3 sum = vec3(0.0);
+	float total = 0.0;
+	for (int i = -8; i <= 8; i++) {
+		float weight = exp(-2.0 * float(i * i) / 64.0);
+		sum += texture(u_bloom, texCoords + step * float(i)).rgb * weight;
+		total += weight;
+	}
+	FragColor = vec4(texture(tex, texCoords).rgb + sum / total * u_intensity, 1.0);
+}
@@ -0,0 +1,24 @@
+#version 330 core
+
+out vec4 FragColor;
+
+in vec2 texCoords;
+
+uniform sampler2D tex;
+// only what's brighter than this glows
+uniform float u_threshold;
+// pixels the glow reaches
+uniform float u_radius;
+
+void main() {
+	// horizontal half of a gaussian over the bright parts, post_bloom does the vertical half
+	vec2 step = vec2(u_radius / 8.0 / float(textureSize(tex, 0).x), 0.0);
+	vec3 sum = vec3(0.0);
+	float total = 0.0;
+	for (int i = -8; i <= 8; i++) {
+		float weight = exp(-2.0 * float(i * i) / 64.0);
+		sum += max(texture(tex, texCoords + step * float(i)).rgb - u_threshold, 0.0) * weight;
+		total += weight;
+	}
+	FragColor = vec4(sum / total, 1.0);
+}
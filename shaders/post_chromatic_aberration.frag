@@ -0,0 +1,14 @@
+#version 330 core
+
+out vec4 FragColor;
+
+in vec2 texCoords;
+
+uniform sampler2D tex;
+// pixels red and blue move apart in the corners
+uniform float u_offset;
+
+void main() {
+	vec2 shift = (texCoords - 0.5) * 2.0 * u_offset / vec2(textureSize(tex, 0));
+	FragColor = vec4(texture(tex, texCoords + shift).r, texture(tex, texCoords).g, texture(tex, texCoords - shift).b, 1.0);
+}
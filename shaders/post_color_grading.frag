@@ -0,0 +1,15 @@
+#version 330 core
+
+out vec4 FragColor;
+
+in vec2 texCoords;
+
+uniform sampler2D tex;
+uniform sampler3D u_lut;
+
+void main() {
+	// sample at texel centres, so 0 and 1 land on the first and last entries
+	float size = float(textureSize(u_lut, 0).x);
+	vec3 color = clamp(texture(tex, texCoords).rgb, 0.0, 1.0);
+	FragColor = vec4(texture(u_lut, color * (size - 1.0) / size + 0.5 / size).rgb, 1.0);
+}
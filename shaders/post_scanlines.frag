@@ -0,0 +1,18 @@
+#version 330 core
+
+out vec4 FragColor;
+
+in vec2 texCoords;
+
+uniform sampler2D tex;
+// how much darker the dark bands are
+uniform float u_intensity;
+// rows per band
+uniform float u_spacing;
+
+void main() {
+	// every other band is dark
+	float row = texCoords.y * float(textureSize(tex, 0).y);
+	float dark = step(1.0, mod(row / u_spacing, 2.0));
+	FragColor = vec4(texture(tex, texCoords).rgb * (1.0 - u_intensity * dark), 1.0);
+}
@@ -0,0 +1,16 @@
+#version 330 core
+
+out vec4 FragColor;
+
+in vec2 texCoords;
+
+uniform sampler2D tex;
+// how much darker the corners get
+uniform float u_strength;
+
+void main() {
+	// 0 in the middle, 0.5 in the corners
+	vec2 offset = texCoords - 0.5;
+	float vignette = clamp(1.0 - u_strength * dot(offset, offset) * 2.0, 0.0, 1.0);
+	FragColor = vec4(texture(tex, texCoords).rgb * vignette, 1.0);
+}
@@ -0,0 +1,27 @@
+#version 330 core
+
+out vec4 FragColor;
+
+in vec2 texCoords;
+
+// last frame's trail
+uniform sampler2D tex;
+// fraction of the trail kept this frame
+uniform float u_keep;
+// size of a texel, 0 skips the blur
+uniform vec2 u_texel;
+
+void main() {
+	vec3 color = texture(tex, texCoords).rgb;
+	if (u_texel.x > 0.0) {
+		// 3x3 binomial kernel
+		vec2 dx = vec2(u_texel.x, 0.0);
+		vec2 dy = vec2(0.0, u_texel.y);
+		color = color * 4.0;
+		color += (texture(tex, texCoords - dx).rgb + texture(tex, texCoords + dx).rgb + texture(tex, texCoords - dy).rgb + texture(tex, texCoords + dy).rgb) * 2.0;
+		color += texture(tex, texCoords - dx - dy).rgb + texture(tex, texCoords + dx - dy).rgb + texture(tex, texCoords - dx + dy).rgb + texture(tex, texCoords + dx + dy).rgb;
+		color /= 16.0;
+	}
+	// round down like the CPU path, rounding to the nearest 8 bit value would leave dim trails forever
+	FragColor = vec4(max(color * u_keep - 0.5 / 255.0, 0.0), 1.0);
+}
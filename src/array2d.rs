@@ -40,6 +40,15 @@ impl<T> Array2D<T> where T: Sized, T: Clone {
 	pub fn as_mut_vec(&mut self) -> &mut Vec<T> {
 		&mut self.data
 	}
+
+	// fills self with the part of `source` starting at (x, y), as far as `source` reaches
+	pub fn copy_region(&mut self, source: &Array2D<T>, x: usize, y: usize) {
+		let w = self.w.min(source.w.saturating_sub(x));
+		for row in 0..self.h.min(source.h.saturating_sub(y)) {
+			let start = x + (y + row) * source.w;
+			self.data[row * self.w..row * self.w + w].clone_from_slice(&source.data[start..start + w]);
+		}
+	}
 }
 
 impl<T> std::ops::Index<(usize, usize)> for Array2D<T> where T: Sized, T: Clone {
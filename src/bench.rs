@@ -0,0 +1,81 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::graphics::Color;
+use crate::physics::*;
+
+const WIDTH: usize = 120;
+const HEIGHT: usize = 68;
+const ITERATIONS: u32 = 20;
+
+fn scene(count: usize) -> Physics {
+	let mut rng = StdRng::seed_from_u64(0);
+	let mut physics = Physics::new(WIDTH, HEIGHT);
+	let color = Color { r: 255, g: 255, b: 255, a: 255 };
+	for _ in 0..count {
+		let x: f32 = rng.gen_range(0.0..WIDTH as f32);
+		let y: f32 = rng.gen_range(0.0..HEIGHT as f32);
+		physics.add_object(x, y, color);
+	}
+	physics
+}
+
+fn time_ms(mut f: impl FnMut()) -> f64 {
+	let start = std::time::Instant::now();
+	for _ in 0..ITERATIONS {
+		f();
+	}
+	(std::time::Instant::now() - start).as_secs_f64() * 1000.0 / ITERATIONS as f64
+}
+
+pub fn collisions(counts: &[usize]) {
+	println!("grid: {}x{}, iterations: {}", WIDTH, HEIGHT, ITERATIONS);
+	for &count in counts {
+		let mut grid = scene(count);
+		let grid_time = time_ms(|| grid.apply_collisions());
+
+		let mut brute_force = scene(count);
+		let brute_force_time = time_ms(|| brute_force.apply_collisions_brute_force());
+
+		println!("objects: {:>6}, grid: {:>9.3}ms, brute_force: {:>9.3}ms, speedup: {:.1}x", count, grid_time, brute_force_time, brute_force_time / grid_time);
+	}
+}
+
+// drops a single object from the top and reports how high it bounces and how much energy is left
+pub fn materials() {
+	let materials = [("default", Material::DEFAULT), ("sand", Material::SAND), ("bouncy", Material::BOUNCY)];
+	for (name, material) in materials.iter() {
+		let mut physics = Physics::new(WIDTH, HEIGHT);
+		physics.config.default_material = *material;
+		let handle = physics.add_object(WIDTH as f32 / 2.0, HEIGHT as f32 - 1.0, Color { r: 255, g: 255, b: 255, a: 255 }).unwrap();
+		physics[handle].vel.x = 10.0;
+
+		let gravity = -physics.config.gravity.y;
+		let start_energy = physics.kinetic_energy() + gravity * (HEIGHT as f32 - 1.0);
+		let mut peak: f32 = 0.0;
+		let mut falling = true;
+		for _ in 0..(30 * 5) {
+			physics.update(1.0 / 30.0);
+			let object = &physics[handle];
+			if falling && object.vel.y > 0.0 {
+				falling = false;
+			} else if !falling && object.vel.y <= 0.0 {
+				peak = peak.max(object.pos.y);
+				falling = true;
+			}
+		}
+		let object = &physics[handle];
+		let energy = physics.kinetic_energy() + gravity * object.pos.y;
+		println!("material: {:>7}, first bounce peak: {:>6.2}, energy left: {:>5.1}%, x speed: {:.2}", name, peak, energy / start_energy * 100.0, object.vel.x);
+	}
+}
+
+// times updates with each thread count
+pub fn threads(count: usize, thread_counts: &[usize]) {
+	for &threads in thread_counts {
+		let mut physics = scene(count);
+		physics.config.threads = threads;
+		let time = time_ms(|| physics.update(1.0 / 30.0));
+		println!("objects: {:>6}, threads: {}, update: {:>9.3}ms", count, threads, time);
+	}
+}
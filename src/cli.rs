@@ -0,0 +1,107 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "desktop_sim", about = "Particle simulation running behind the desktop icons", version)]
+pub struct Cli {
+	// runs on the desktop with the default options when missing
+	#[command(subcommand)]
+	pub command: Option<Command>
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+	#[command(about = "Run the simulation on the desktop")]
+	Run(RunArgs),
+	#[command(about = "List the monitors the desktop backend finds")]
+	ListMonitors(ListMonitorsArgs),
+	#[command(about = "Run the scenes headless for a number of steps and report update timings")]
+	Bench(BenchArgs),
+	#[command(about = "Run the scenes headless and save frames as PPM images")]
+	Render(RenderArgs)
+}
+
+#[derive(Args, Default)]
+pub struct SceneArgs {
+	#[arg(long, help = "Configuration file [default: $DESKTOP_SIM_CONFIG or desktop_sim.toml]")]
+	pub config: Option<PathBuf>,
+	#[arg(long, help = "Only simulate this monitor, by name or index, can be repeated")]
+	pub monitor: Vec<String>,
+	#[arg(long, value_parser = clap::value_parser!(u32).range(1..), help = "Monitor pixels per simulation cell, overrides every scene's downscale")]
+	pub scale: Option<u32>,
+	#[arg(long, help = "Seed for emitters, colours and force fields, overrides the config")]
+	pub seed: Option<u64>
+}
+
+#[derive(Args, Default)]
+pub struct RunArgs {
+	#[command(flatten)]
+	pub scene: SceneArgs,
+	#[arg(long, value_parser = non_negative, help = "Seconds to run for, 0 runs forever")]
+	pub duration: Option<f32>,
+	#[arg(long, value_parser = positive, help = "Most frames drawn per second")]
+	pub fps_cap: Option<f32>,
+	#[arg(long, help = "Run on a fake platform without drawing anything")]
+	pub headless: bool,
+	#[arg(long, value_enum, value_delimiter = ',', requires = "headless", help = "Desktop states the fake platform reports, to try the pause policy")]
+	pub fake_state: Vec<FakeState>,
+	#[arg(long, help = "Print monitors as they are added, removed or changed, and desktop state changes")]
+	pub verbose: bool
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum FakeState {
+	Obscured,
+	Locked,
+	OnBattery,
+	DisplayOff
+}
+
+#[derive(Args)]
+pub struct ListMonitorsArgs {
+	#[arg(long, help = "Print JSON instead of a table")]
+	pub json: bool
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+	#[command(flatten)]
+	pub scene: SceneArgs,
+	#[arg(long, default_value_t = 300, help = "Updates to time")]
+	pub steps: usize,
+	#[arg(long, help = "Run the collision, material and threading micro benchmarks instead")]
+	pub micro: bool
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+	#[command(flatten)]
+	pub scene: SceneArgs,
+	#[arg(short, long, help = "Directory the frames are written to")]
+	pub output: PathBuf,
+	#[arg(long, default_value_t = 100, help = "Frames to save")]
+	pub frames: usize,
+	#[arg(long, help = "Updates between saved frames [default: frame_interval from the config]")]
+	pub every: Option<usize>,
+	#[arg(long, default_value_t = 1920, help = "Width of the headless monitor")]
+	pub width: i32,
+	#[arg(long, default_value_t = 1080, help = "Height of the headless monitor")]
+	pub height: i32
+}
+
+fn positive(value: &str) -> Result<f32, String> {
+	match value.parse::<f32>() {
+		Ok(number) if number > 0.0 => Ok(number),
+		Ok(_) => Err("must be greater than 0".to_owned()),
+		Err(e) => Err(e.to_string())
+	}
+}
+
+fn non_negative(value: &str) -> Result<f32, String> {
+	match value.parse::<f32>() {
+		Ok(number) if number >= 0.0 => Ok(number),
+		Ok(_) => Err("must not be negative".to_owned()),
+		Err(e) => Err(e.to_string())
+	}
+}
@@ -0,0 +1,990 @@
+use serde::Deserialize;
+
+use crate::graphics::Color;
+use crate::monitor::Monitor;
+use crate::physics::*;
+use crate::platform::DesktopState;
+use crate::post::{Lut, PostEffect};
+use crate::render::{DrawMode, Effects, Trails};
+
+pub const DEFAULT_PATH: &str = "desktop_sim.toml";
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	// seconds to run for, 0 runs forever
+	pub duration: f32,
+	// simulated seconds per update
+	pub timestep: f32,
+	// frames drawn per second at most, uncapped when missing
+	pub fps_cap: Option<f32>,
+	// updates caught up in one frame before the simulation slows down instead
+	pub max_updates_per_frame: usize,
+	// draw objects between their last two positions when frames fall between updates
+	pub interpolate: bool,
+	pub clear_color: [u8; 3],
+	// the cpu renderer saves every nth frame
+	pub frame_interval: usize,
+	pub pause: PauseConfig,
+	// what happens to the particles when a monitor changes resolution
+	pub resize: ResizeName,
+	pub layout: Layout,
+	// objects cross over to monitors that share an edge instead of bouncing off it
+	pub connect_monitors: bool,
+	// seeds emitters, colours and force fields so runs repeat, random when missing
+	pub seed: Option<u64>,
+	// used by monitors without a [[monitor]] entry
+	pub scene: SceneConfig,
+	#[serde(rename = "monitor")]
+	pub monitors: Vec<MonitorConfig>
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			duration: 1000.0,
+			timestep: 1.0 / 30.0,
+			fps_cap: None,
+			max_updates_per_frame: 5,
+			interpolate: true,
+			clear_color: [26, 26, 51],
+			frame_interval: 30,
+			pause: PauseConfig::default(),
+			resize: ResizeName::Rescale,
+			layout: Layout::Separate,
+			connect_monitors: true,
+			seed: None,
+			scene: SceneConfig::default(),
+			monitors: Vec::new()
+		}
+	}
+}
+
+// ordered from least to most restrictive
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseAction {
+	Run,
+	// fewer frames and at most one update per frame, the simulation runs slower
+	Throttle,
+	// no updates and no drawing
+	Pause
+}
+
+// what to do in each desktop state, the most restrictive action of the active states wins
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PauseConfig {
+	pub obscured: PauseAction,
+	pub locked: PauseAction,
+	pub on_battery: PauseAction,
+	pub display_off: PauseAction,
+	// frames per second while throttled
+	pub throttle_fps: f32,
+	// seconds between asking the platform for the desktop state
+	pub check_interval: f32
+}
+
+impl Default for PauseConfig {
+	fn default() -> Self {
+		PauseConfig {
+			obscured: PauseAction::Pause,
+			locked: PauseAction::Pause,
+			on_battery: PauseAction::Throttle,
+			display_off: PauseAction::Pause,
+			throttle_fps: 10.0,
+			check_interval: 1.0
+		}
+	}
+}
+
+impl PauseConfig {
+	pub fn action(&self, state: DesktopState) -> PauseAction {
+		[
+			(state.obscured, self.obscured),
+			(state.locked, self.locked),
+			(state.on_battery, self.on_battery),
+			(state.display_off, self.display_off)
+		].iter().filter(|(active, _)| *active).map(|(_, action)| *action).max().unwrap_or(PauseAction::Run)
+	}
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+	// a simulation per monitor
+	Separate,
+	// one simulation over the bounding box of all monitors with [scene], the space between monitors is solid
+	Unified
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeName {
+	Rescale,
+	Clip
+}
+
+// matched against Monitor::name first, then against the monitor index
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonitorConfig {
+	pub name: Option<String>,
+	pub index: Option<usize>,
+	#[serde(default)]
+	pub scene: SceneConfig
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SceneKind {
+	// one emitter in the middle
+	Fountain,
+	// emitters along the top edge
+	Rain,
+	// a cloth pinned along the top edge
+	Cloth,
+	// a V that pours the particles from two emitters into one stream
+	Funnel,
+	// rows of pegs above a row of bins, a Galton board
+	Pegboard,
+	// two bulbs joined by a narrow neck, the top one filled from an emitter
+	Hourglass,
+	// only the configured emitters
+	Empty
+}
+
+// the part of the monitor a scene covers
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FillArea {
+	Full,
+	// leaves taskbars and docks alone
+	Work
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawName {
+	Texture,
+	Circles
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrailsSection {
+	// exponential decay per second, 0 never fades
+	pub fade_rate: f32,
+	// adds particles onto the trail instead of keeping the brighter of the two
+	pub additive: bool,
+	// softens the trail a little every frame
+	pub blur: bool
+}
+
+impl Default for TrailsSection {
+	fn default() -> Self {
+		TrailsSection {
+			fade_rate: 3.0,
+			additive: false,
+			blur: false
+		}
+	}
+}
+
+// one post-processing pass, `effect` says which
+#[derive(Deserialize)]
+#[serde(tag = "effect", rename_all = "snake_case")]
+pub enum PostSection {
+	Bloom(BloomSection),
+	Vignette(VignetteSection),
+	ChromaticAberration(ChromaticAberrationSection),
+	ColorGrading(ColorGradingSection),
+	Scanlines(ScanlinesSection)
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BloomSection {
+	// brightness from 0 to 1 above which things glow
+	pub threshold: f32,
+	pub intensity: f32,
+	// pixels the glow reaches
+	pub radius: f32
+}
+
+impl Default for BloomSection {
+	fn default() -> Self {
+		BloomSection {
+			threshold: 0.6,
+			intensity: 1.0,
+			radius: 16.0
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VignetteSection {
+	// how much darker the corners get, from 0 to 1
+	pub strength: f32
+}
+
+impl Default for VignetteSection {
+	fn default() -> Self {
+		VignetteSection {
+			strength: 0.5
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChromaticAberrationSection {
+	// pixels red and blue move apart in the corners
+	pub offset: f32
+}
+
+impl Default for ChromaticAberrationSection {
+	fn default() -> Self {
+		ChromaticAberrationSection {
+			offset: 3.0
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorGradingSection {
+	// .cube file with a 3D lookup table
+	pub lut: std::path::PathBuf
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanlinesSection {
+	// how much darker the dark lines are, from 0 to 1
+	pub intensity: f32,
+	// pixel rows per line
+	pub spacing: f32
+}
+
+impl Default for ScanlinesSection {
+	fn default() -> Self {
+		ScanlinesSection {
+			intensity: 0.3,
+			spacing: 2.0
+		}
+	}
+}
+
+impl PostSection {
+	// reads the lookup table of colour grading
+	pub fn effect(&self) -> Result<PostEffect, String> {
+		Ok(match self {
+			PostSection::Bloom(bloom) => PostEffect::Bloom { threshold: bloom.threshold, intensity: bloom.intensity, radius: bloom.radius },
+			PostSection::Vignette(vignette) => PostEffect::Vignette { strength: vignette.strength },
+			PostSection::ChromaticAberration(aberration) => PostEffect::ChromaticAberration { offset: aberration.offset },
+			PostSection::ColorGrading(grading) => PostEffect::ColorGrading { lut: Lut::load(&grading.lut)? },
+			PostSection::Scanlines(scanlines) => PostEffect::Scanlines { intensity: scanlines.intensity, spacing: scanlines.spacing }
+		})
+	}
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum FalloffName {
+	None,
+	Linear,
+	InverseSquare
+}
+
+// a force acting on every particle, `kind` says which
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ForceFieldSection {
+	Point(PointFieldSection),
+	Vortex(VortexSection),
+	Wind(WindSection),
+	Turbulence(TurbulenceSection)
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PointFieldSection {
+	// fraction of the world size, [0, 0] is the bottom left
+	pub position: [f32; 2],
+	// cells per second squared towards the point, negative pushes away
+	pub strength: f32,
+	// simulation cells the field reaches
+	pub radius: f32,
+	pub falloff: FalloffName
+}
+
+impl Default for PointFieldSection {
+	fn default() -> Self {
+		PointFieldSection {
+			position: [0.5, 0.5],
+			strength: 100.0,
+			radius: 30.0,
+			falloff: FalloffName::Linear
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VortexSection {
+	pub position: [f32; 2],
+	// counter-clockwise, negative spins clockwise
+	pub strength: f32,
+	pub radius: f32
+}
+
+impl Default for VortexSection {
+	fn default() -> Self {
+		VortexSection {
+			position: [0.5, 0.5],
+			strength: 100.0,
+			radius: 30.0
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindSection {
+	pub acceleration: [f32; 2],
+	// how much the wind varies, 0.5 blows between half and one and a half times as hard
+	pub gust_strength: f32,
+	// gusts per second
+	pub gust_frequency: f32
+}
+
+impl Default for WindSection {
+	fn default() -> Self {
+		WindSection {
+			acceleration: [20.0, 0.0],
+			gust_strength: 0.5,
+			gust_frequency: 0.5
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TurbulenceSection {
+	pub strength: f32,
+	// simulation cells across a swirl
+	pub scale: f32,
+	// how fast the swirls drift
+	pub speed: f32
+}
+
+impl Default for TurbulenceSection {
+	fn default() -> Self {
+		TurbulenceSection {
+			strength: 30.0,
+			scale: 10.0,
+			speed: 0.5
+		}
+	}
+}
+
+impl ForceFieldSection {
+	// `seed` varies the gusts and swirls
+	pub fn force_field(&self, size: glm::Vec2, seed: u32) -> ForceField {
+		let point = |point: [f32; 2]| glm::vec2(point[0] * size.x, point[1] * size.y);
+		match self {
+			ForceFieldSection::Point(field) => ForceField::Point {
+				position: point(field.position),
+				strength: field.strength,
+				radius: field.radius,
+				falloff: match field.falloff {
+					FalloffName::None => Falloff::None,
+					FalloffName::Linear => Falloff::Linear,
+					FalloffName::InverseSquare => Falloff::InverseSquare
+				}
+			},
+			ForceFieldSection::Vortex(field) => ForceField::Vortex { position: point(field.position), strength: field.strength, radius: field.radius },
+			ForceFieldSection::Wind(field) => ForceField::Wind {
+				acceleration: glm::vec2(field.acceleration[0], field.acceleration[1]),
+				gust_strength: field.gust_strength,
+				gust_frequency: field.gust_frequency,
+				seed
+			},
+			ForceFieldSection::Turbulence(field) => ForceField::Turbulence { strength: field.strength, scale: field.scale, speed: field.speed, seed }
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneConfig {
+	pub kind: SceneKind,
+	pub area: FillArea,
+	pub draw: DrawName,
+	// particles leave fading trails instead of the monitor being cleared every frame
+	pub trails: Option<TrailsSection>,
+	// fullscreen passes over the monitor, in order
+	pub post: Vec<PostSection>,
+	// monitor pixels per simulation cell
+	pub downscale: usize,
+	// "#rrggbb" colours used by emitters with color = "palette"
+	pub palette: Vec<String>,
+	pub physics: PhysicsSection,
+	// replaces the emitters the scene kind would add
+	pub emitters: Option<Vec<EmitterConfig>>,
+	// replaces the colliders the scene kind would add
+	pub colliders: Option<Vec<ColliderConfig>>,
+	pub force_fields: Vec<ForceFieldSection>,
+	pub ropes: Vec<RopeConfig>,
+	pub soft_bodies: Vec<SoftBodyConfig>,
+	// regions that despawn the particles entering them
+	pub drains: Vec<DrainConfig>
+}
+
+impl Default for SceneConfig {
+	fn default() -> Self {
+		SceneConfig {
+			kind: SceneKind::Fountain,
+			area: FillArea::Full,
+			draw: DrawName::Texture,
+			trails: None,
+			post: Vec::new(),
+			downscale: 16,
+			palette: Vec::new(),
+			physics: PhysicsSection::default(),
+			emitters: None,
+			colliders: None,
+			force_fields: Vec::new(),
+			ropes: Vec::new(),
+			soft_bodies: Vec::new(),
+			drains: Vec::new()
+		}
+	}
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum MaterialName {
+	Default,
+	Sand,
+	Bouncy
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyName {
+	DropOldest,
+	RefuseSpawn
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhysicsSection {
+	pub step_count: usize,
+	pub max_step_count: usize,
+	pub gravity: [f32; 2],
+	pub collision_iterations: usize,
+	pub constraint_iterations: usize,
+	pub velocity_damping: f32,
+	pub air_drag: f32,
+	pub material: MaterialName,
+	// override the named material
+	pub restitution: Option<f32>,
+	pub friction: Option<f32>,
+	pub max_velocity: Option<f32>,
+	pub deterministic: bool,
+	pub max_objects: Option<usize>,
+	pub population_policy: PolicyName,
+	pub lifetime: Option<f32>,
+	pub fade_time: f32
+}
+
+impl Default for PhysicsSection {
+	fn default() -> Self {
+		let config = PhysicsConfig::default();
+		PhysicsSection {
+			step_count: config.step_count,
+			max_step_count: config.max_step_count,
+			gravity: [config.gravity.x, config.gravity.y],
+			collision_iterations: config.collision_iterations,
+			constraint_iterations: config.constraint_iterations,
+			velocity_damping: config.velocity_damping,
+			air_drag: config.air_drag,
+			material: MaterialName::Default,
+			restitution: None,
+			friction: None,
+			max_velocity: config.max_velocity,
+			deterministic: config.deterministic,
+			max_objects: config.max_objects,
+			population_policy: PolicyName::DropOldest,
+			lifetime: config.default_lifetime,
+			fade_time: config.fade_time
+		}
+	}
+}
+
+impl PhysicsSection {
+	pub fn material(&self) -> Material {
+		let mut material = match self.material {
+			MaterialName::Default => Material::DEFAULT,
+			MaterialName::Sand => Material::SAND,
+			MaterialName::Bouncy => Material::BOUNCY
+		};
+		if let Some(restitution) = self.restitution {
+			material.restitution = restitution;
+		}
+		if let Some(friction) = self.friction {
+			material.friction = friction;
+		}
+		material
+	}
+
+	pub fn apply(&self, config: &mut PhysicsConfig) {
+		config.step_count = self.step_count;
+		config.max_step_count = self.max_step_count;
+		config.gravity = glm::vec2(self.gravity[0], self.gravity[1]);
+		config.collision_iterations = self.collision_iterations;
+		config.constraint_iterations = self.constraint_iterations;
+		config.velocity_damping = self.velocity_damping;
+		config.air_drag = self.air_drag;
+		config.default_material = self.material();
+		config.max_velocity = self.max_velocity;
+		config.deterministic = self.deterministic;
+		config.max_objects = self.max_objects;
+		config.population_policy = match self.population_policy {
+			PolicyName::DropOldest => PopulationPolicy::DropOldest,
+			PolicyName::RefuseSpawn => PopulationPolicy::RefuseSpawn
+		};
+		config.default_lifetime = self.lifetime;
+		config.fade_time = self.fade_time;
+	}
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct BurstConfig {
+	pub count: usize,
+	pub interval: f32
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmitterConfig {
+	// fraction of the world size, [0, 0] is the bottom left
+	pub position: [f32; 2],
+	pub direction: [f32; 2],
+	// half angle of the spawn cone in degrees
+	pub spread: f32,
+	pub speed: [f32; 2],
+	// objects per second, ignored when burst is set
+	pub rate: f32,
+	pub burst: Option<BurstConfig>,
+	// "cycle", "random", "palette" or "#rrggbb"
+	pub color: String,
+	pub radius: f32,
+	pub mass: f32,
+	pub lifetime: Option<f32>,
+	pub max_count: Option<usize>
+}
+
+impl Default for EmitterConfig {
+	fn default() -> Self {
+		EmitterConfig {
+			position: [0.5, 0.5],
+			direction: [-1.0, 0.0],
+			spread: 0.0,
+			speed: [0.2, 0.2],
+			rate: 3.0,
+			burst: None,
+			color: "cycle".to_string(),
+			radius: DEFAULT_RADIUS,
+			mass: DEFAULT_MASS,
+			lifetime: None,
+			max_count: None
+		}
+	}
+}
+
+// a chain of particles, pinned at `start` unless `pinned` is off
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RopeConfig {
+	// fractions of the world size, [0, 0] is the bottom left
+	pub start: [f32; 2],
+	pub end: [f32; 2],
+	pub segments: usize,
+	// fraction of the stretch corrected per iteration, from 0 to 1
+	pub stiffness: f32,
+	// how much the rope resists bending, 0 lets it hang freely
+	pub bend_stiffness: f32,
+	pub pinned: bool,
+	// "#rrggbb", the first palette colour or a random one when missing
+	pub color: Option<String>
+}
+
+impl Default for RopeConfig {
+	fn default() -> Self {
+		RopeConfig {
+			start: [0.3, 0.8],
+			end: [0.7, 0.8],
+			segments: 20,
+			stiffness: 1.0,
+			bend_stiffness: 0.0,
+			pinned: true,
+			color: None
+		}
+	}
+}
+
+// a ring of particles that tries to keep its area
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoftBodyConfig {
+	// fraction of the world size
+	pub center: [f32; 2],
+	// simulation cells
+	pub radius: f32,
+	pub count: usize,
+	// area kept as a multiple of the starting area
+	pub pressure: f32,
+	pub stiffness: f32,
+	pub color: Option<String>
+}
+
+impl Default for SoftBodyConfig {
+	fn default() -> Self {
+		SoftBodyConfig {
+			center: [0.5, 0.6],
+			radius: 6.0,
+			count: 20,
+			pressure: 1.0,
+			stiffness: 0.5,
+			color: None
+		}
+	}
+}
+
+// a rectangle in fractions of the world size with [0, 0] at the bottom left
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DrainConfig {
+	pub min: [f32; 2],
+	pub max: [f32; 2]
+}
+
+impl Default for DrainConfig {
+	fn default() -> Self {
+		DrainConfig {
+			min: [0.0, 0.0],
+			max: [1.0, 0.05]
+		}
+	}
+}
+
+// a static shape particles bounce off, points are fractions of the world size with [0, 0]
+// at the bottom left, radii are in simulation cells like emitter radii
+#[derive(Deserialize, Clone)]
+#[serde(tag = "shape", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ColliderConfig {
+	Segment { a: [f32; 2], b: [f32; 2] },
+	Circle { center: [f32; 2], radius: f32 },
+	Capsule { a: [f32; 2], b: [f32; 2], radius: f32 },
+	// convex, in either winding order
+	Polygon { points: Vec<[f32; 2]> }
+}
+
+pub fn parse_color(text: &str) -> Option<Color> {
+	let hex = text.strip_prefix('#')?;
+	if hex.len() != 6 || !hex.is_ascii() {
+		return None;
+	}
+	let channel = |i: usize| u8::from_str_radix(&hex[i..(i + 2)], 16).ok();
+	Some(Color { r: channel(0)?, g: channel(2)?, b: channel(4)?, a: 255 })
+}
+
+fn check(ok: bool, key: &str, message: &str) -> Result<(), String> {
+	if ok { Ok(()) } else { Err(format!("{}: {}", key, message)) }
+}
+
+impl Config {
+	// missing file means the defaults
+	pub fn load(path: &std::path::Path) -> Result<Config, String> {
+		match std::fs::read_to_string(path) {
+			Ok(text) => Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+			Err(e) => Err(format!("Failed to read {}: {}", path.display(), e))
+		}
+	}
+
+	pub fn parse(text: &str) -> Result<Config, String> {
+		let config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+		config.validate()?;
+		Ok(config)
+	}
+
+	pub fn validate(&self) -> Result<(), String> {
+		check(self.duration >= 0.0, "duration", "must not be negative")?;
+		check(self.timestep > 0.0, "timestep", "must be greater than 0")?;
+		check(self.fps_cap.is_none_or(|fps_cap| fps_cap > 0.0), "fps_cap", "must be greater than 0")?;
+		check(self.max_updates_per_frame > 0, "max_updates_per_frame", "must be at least 1")?;
+		check(self.frame_interval > 0, "frame_interval", "must be at least 1")?;
+		check(self.pause.throttle_fps > 0.0, "pause.throttle_fps", "must be greater than 0")?;
+		check(self.pause.check_interval > 0.0, "pause.check_interval", "must be greater than 0")?;
+		self.scene.validate("scene")?;
+		for (i, monitor) in self.monitors.iter().enumerate() {
+			check(monitor.name.is_some() || monitor.index.is_some(), &format!("monitor[{}]", i), "needs a name or an index")?;
+			monitor.scene.validate(&format!("monitor[{}].scene", i))?;
+		}
+		Ok(())
+	}
+
+	pub fn scene_for(&self, index: usize, monitor: &Monitor) -> &SceneConfig {
+		self.monitors.iter()
+			.find(|config| config.name.as_deref() == Some(monitor.name.as_str()))
+			.or_else(|| self.monitors.iter().find(|config| config.name.is_none() && config.index == Some(index)))
+			.map_or(&self.scene, |config| &config.scene)
+	}
+
+	pub fn resize_mode(&self) -> ResizeMode {
+		match self.resize {
+			ResizeName::Rescale => ResizeMode::Rescale,
+			ResizeName::Clip => ResizeMode::Clip
+		}
+	}
+
+	pub fn clear_color(&self) -> Color {
+		Color { r: self.clear_color[0], g: self.clear_color[1], b: self.clear_color[2], a: 255 }
+	}
+}
+
+impl SceneConfig {
+	pub fn draw_mode(&self) -> DrawMode {
+		match self.draw {
+			DrawName::Texture => DrawMode::Texture,
+			DrawName::Circles => DrawMode::Circles
+		}
+	}
+
+	// loads the lookup tables of colour grading passes, so it can fail
+	pub fn effects(&self) -> Result<Effects, String> {
+		Ok(Effects {
+			trails: self.trails.as_ref().map(|trails| Trails { fade_rate: trails.fade_rate, additive: trails.additive, blur: trails.blur }),
+			post: self.post.iter().map(|post| post.effect()).collect::<Result<Vec<PostEffect>, String>>()?
+		})
+	}
+
+	fn validate(&self, key: &str) -> Result<(), String> {
+		check(self.downscale > 0, &format!("{}.downscale", key), "must be at least 1")?;
+		if let Some(trails) = self.trails.as_ref() {
+			check(trails.fade_rate >= 0.0, &format!("{}.trails.fade_rate", key), "must not be negative")?;
+		}
+		for (i, post) in self.post.iter().enumerate() {
+			let key_post = format!("{}.post[{}]", key, i);
+			match post {
+				PostSection::Bloom(bloom) => {
+					check((0.0..=1.0).contains(&bloom.threshold), &format!("{}.threshold", key_post), "must be between 0 and 1")?;
+					check(bloom.intensity >= 0.0, &format!("{}.intensity", key_post), "must not be negative")?;
+					check(bloom.radius > 0.0, &format!("{}.radius", key_post), "must be greater than 0")?;
+				},
+				PostSection::Vignette(vignette) => check((0.0..=1.0).contains(&vignette.strength), &format!("{}.strength", key_post), "must be between 0 and 1")?,
+				PostSection::ChromaticAberration(aberration) => check(aberration.offset >= 0.0, &format!("{}.offset", key_post), "must not be negative")?,
+				PostSection::ColorGrading(_) => {},
+				PostSection::Scanlines(scanlines) => {
+					check((0.0..=1.0).contains(&scanlines.intensity), &format!("{}.intensity", key_post), "must be between 0 and 1")?;
+					check(scanlines.spacing >= 1.0, &format!("{}.spacing", key_post), "must be at least 1")?;
+				}
+			}
+		}
+		for (i, color) in self.palette.iter().enumerate() {
+			check(parse_color(color).is_some(), &format!("{}.palette[{}]", key, i), "expected a colour like \"#ff8000\"")?;
+		}
+
+		let physics = &self.physics;
+		let key_physics = format!("{}.physics", key);
+		check(physics.step_count > 0, &format!("{}.step_count", key_physics), "must be at least 1")?;
+		check(physics.max_step_count >= physics.step_count, &format!("{}.max_step_count", key_physics), "must be at least step_count")?;
+		check(physics.velocity_damping >= 0.0, &format!("{}.velocity_damping", key_physics), "must not be negative")?;
+		check(physics.air_drag >= 0.0, &format!("{}.air_drag", key_physics), "must not be negative")?;
+		check(physics.restitution.is_none_or(|restitution| (0.0..=1.0).contains(&restitution)), &format!("{}.restitution", key_physics), "must be between 0 and 1")?;
+		check(physics.friction.is_none_or(|friction| friction >= 0.0), &format!("{}.friction", key_physics), "must not be negative")?;
+		check(physics.max_velocity.is_none_or(|velocity| velocity > 0.0), &format!("{}.max_velocity", key_physics), "must be greater than 0")?;
+		check(physics.lifetime.is_none_or(|lifetime| lifetime > 0.0), &format!("{}.lifetime", key_physics), "must be greater than 0")?;
+		check(physics.fade_time >= 0.0, &format!("{}.fade_time", key_physics), "must not be negative")?;
+
+		for (i, emitter) in self.emitters.iter().flatten().enumerate() {
+			let key_emitter = format!("{}.emitters[{}]", key, i);
+			check((0.0..=1.0).contains(&emitter.position[0]) && (0.0..=1.0).contains(&emitter.position[1]), &format!("{}.position", key_emitter), "must be between [0, 0] and [1, 1]")?;
+			check(emitter.direction != [0.0, 0.0], &format!("{}.direction", key_emitter), "must not be zero")?;
+			check((0.0..=180.0).contains(&emitter.spread), &format!("{}.spread", key_emitter), "must be between 0 and 180 degrees")?;
+			check(0.0 <= emitter.speed[0] && emitter.speed[0] <= emitter.speed[1], &format!("{}.speed", key_emitter), "expected [min, max] with 0 <= min <= max")?;
+			check(emitter.rate > 0.0, &format!("{}.rate", key_emitter), "must be greater than 0")?;
+			if let Some(burst) = emitter.burst {
+				check(burst.count > 0, &format!("{}.burst.count", key_emitter), "must be at least 1")?;
+				check(burst.interval > 0.0, &format!("{}.burst.interval", key_emitter), "must be greater than 0")?;
+			}
+			let color_ok = match emitter.color.as_str() {
+				"cycle" | "random" => true,
+				"palette" => !self.palette.is_empty(),
+				color => parse_color(color).is_some()
+			};
+			check(color_ok, &format!("{}.color", key_emitter), "expected \"cycle\", \"random\", \"palette\" with a palette set, or a colour like \"#ff8000\"")?;
+			check(emitter.radius > 0.0, &format!("{}.radius", key_emitter), "must be greater than 0")?;
+			check(emitter.mass > 0.0, &format!("{}.mass", key_emitter), "must be greater than 0")?;
+			check(emitter.lifetime.is_none_or(|lifetime| lifetime > 0.0), &format!("{}.lifetime", key_emitter), "must be greater than 0")?;
+		}
+
+		let inside = |point: &[f32; 2]| (0.0..=1.0).contains(&point[0]) && (0.0..=1.0).contains(&point[1]);
+		for (i, collider) in self.colliders.iter().flatten().enumerate() {
+			let key_collider = format!("{}.colliders[{}]", key, i);
+			match collider {
+				ColliderConfig::Segment { a, b } => {
+					check(inside(a), &format!("{}.a", key_collider), "must be between [0, 0] and [1, 1]")?;
+					check(inside(b), &format!("{}.b", key_collider), "must be between [0, 0] and [1, 1]")?;
+				},
+				ColliderConfig::Circle { center, radius } => {
+					check(inside(center), &format!("{}.center", key_collider), "must be between [0, 0] and [1, 1]")?;
+					check(*radius > 0.0, &format!("{}.radius", key_collider), "must be greater than 0")?;
+				},
+				ColliderConfig::Capsule { a, b, radius } => {
+					check(inside(a), &format!("{}.a", key_collider), "must be between [0, 0] and [1, 1]")?;
+					check(inside(b), &format!("{}.b", key_collider), "must be between [0, 0] and [1, 1]")?;
+					check(*radius > 0.0, &format!("{}.radius", key_collider), "must be greater than 0")?;
+				},
+				ColliderConfig::Polygon { points } => {
+					check(points.iter().all(inside), &format!("{}.points", key_collider), "must be between [0, 0] and [1, 1]")?;
+					let polygon = Collider::polygon(points.iter().map(|point| glm::vec2(point[0], point[1])).collect());
+					check(polygon.is_ok(), &format!("{}.points", key_collider), "expected at least 3 points making a convex shape with an area")?;
+				}
+			}
+		}
+
+		for (i, force_field) in self.force_fields.iter().enumerate() {
+			let key_field = format!("{}.force_fields[{}]", key, i);
+			match force_field {
+				ForceFieldSection::Point(PointFieldSection { position, radius, .. }) | ForceFieldSection::Vortex(VortexSection { position, radius, .. }) => {
+					check(inside(position), &format!("{}.position", key_field), "must be between [0, 0] and [1, 1]")?;
+					check(*radius > 0.0, &format!("{}.radius", key_field), "must be greater than 0")?;
+				},
+				ForceFieldSection::Wind(wind) => {
+					check(wind.gust_strength >= 0.0, &format!("{}.gust_strength", key_field), "must not be negative")?;
+					check(wind.gust_frequency >= 0.0, &format!("{}.gust_frequency", key_field), "must not be negative")?;
+				},
+				ForceFieldSection::Turbulence(turbulence) => {
+					check(turbulence.scale > 0.0, &format!("{}.scale", key_field), "must be greater than 0")?;
+					check(turbulence.speed >= 0.0, &format!("{}.speed", key_field), "must not be negative")?;
+				}
+			}
+		}
+
+		let color_ok = |color: &Option<String>| color.as_deref().is_none_or(|color| parse_color(color).is_some());
+		for (i, rope) in self.ropes.iter().enumerate() {
+			let key_rope = format!("{}.ropes[{}]", key, i);
+			check(inside(&rope.start), &format!("{}.start", key_rope), "must be between [0, 0] and [1, 1]")?;
+			check(inside(&rope.end), &format!("{}.end", key_rope), "must be between [0, 0] and [1, 1]")?;
+			check(rope.segments > 0, &format!("{}.segments", key_rope), "must be at least 1")?;
+			check(rope.stiffness > 0.0 && rope.stiffness <= 1.0, &format!("{}.stiffness", key_rope), "must be greater than 0 and at most 1")?;
+			check((0.0..=1.0).contains(&rope.bend_stiffness), &format!("{}.bend_stiffness", key_rope), "must be between 0 and 1")?;
+			check(color_ok(&rope.color), &format!("{}.color", key_rope), "expected a colour like \"#ff8000\"")?;
+		}
+		for (i, soft_body) in self.soft_bodies.iter().enumerate() {
+			let key_body = format!("{}.soft_bodies[{}]", key, i);
+			check(inside(&soft_body.center), &format!("{}.center", key_body), "must be between [0, 0] and [1, 1]")?;
+			check(soft_body.radius > 0.0, &format!("{}.radius", key_body), "must be greater than 0")?;
+			check(soft_body.count >= 3, &format!("{}.count", key_body), "must be at least 3")?;
+			check(soft_body.pressure > 0.0, &format!("{}.pressure", key_body), "must be greater than 0")?;
+			check(soft_body.stiffness > 0.0 && soft_body.stiffness <= 1.0, &format!("{}.stiffness", key_body), "must be greater than 0 and at most 1")?;
+			check(color_ok(&soft_body.color), &format!("{}.color", key_body), "expected a colour like \"#ff8000\"")?;
+		}
+		for (i, drain) in self.drains.iter().enumerate() {
+			let key_drain = format!("{}.drains[{}]", key, i);
+			check(inside(&drain.min), &format!("{}.min", key_drain), "must be between [0, 0] and [1, 1]")?;
+			check(inside(&drain.max), &format!("{}.max", key_drain), "must be between [0, 0] and [1, 1]")?;
+			check(drain.min[0] < drain.max[0] && drain.min[1] < drain.max[1], &format!("{}.max", key_drain), "must be above and right of min")?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn state(obscured: bool, locked: bool, on_battery: bool, display_off: bool) -> DesktopState {
+		DesktopState { obscured, locked, on_battery, display_off }
+	}
+
+	#[test]
+	fn default_pause_actions_per_state() {
+		let pause = PauseConfig::default();
+		assert_eq!(pause.action(state(false, false, false, false)), PauseAction::Run);
+		assert_eq!(pause.action(state(true, false, false, false)), PauseAction::Pause);
+		assert_eq!(pause.action(state(false, true, false, false)), PauseAction::Pause);
+		assert_eq!(pause.action(state(false, false, true, false)), PauseAction::Throttle);
+		assert_eq!(pause.action(state(false, false, false, true)), PauseAction::Pause);
+	}
+
+	#[test]
+	fn the_most_restrictive_active_state_wins() {
+		let pause = Config::parse("[pause]\nobscured = \"throttle\"\nlocked = \"run\"\non_battery = \"pause\"\ndisplay_off = \"run\"").unwrap().pause;
+		assert_eq!(pause.action(state(true, false, false, false)), PauseAction::Throttle);
+		assert_eq!(pause.action(state(false, true, false, true)), PauseAction::Run);
+		assert_eq!(pause.action(state(true, true, false, false)), PauseAction::Throttle);
+		assert_eq!(pause.action(state(true, false, true, false)), PauseAction::Pause);
+
+		// every combination, against the actions of the single states
+		for bits in 0..16 {
+			let active = [bits & 1 != 0, bits & 2 != 0, bits & 4 != 0, bits & 8 != 0];
+			let expected = [pause.obscured, pause.locked, pause.on_battery, pause.display_off].iter()
+				.zip(active.iter()).filter(|(_, active)| **active).map(|(action, _)| *action).max().unwrap_or(PauseAction::Run);
+			assert_eq!(pause.action(state(active[0], active[1], active[2], active[3])), expected, "state {:?}", active);
+		}
+	}
+
+	#[test]
+	fn pause_rates_must_be_positive() {
+		assert!(Config::parse("[pause]\nthrottle_fps = 0.0").is_err());
+		assert!(Config::parse("[pause]\nthrottle_fps = -5.0").is_err());
+		assert!(Config::parse("[pause]\nthrottle_fps = 5.0").is_ok());
+	}
+
+	fn error(text: &str) -> String {
+		match Config::parse(text) {
+			Ok(_) => panic!("accepted {:?}", text),
+			Err(e) => e
+		}
+	}
+
+	#[test]
+	fn the_example_config_is_valid() {
+		if let Err(e) = Config::parse(include_str!("../desktop_sim.example.toml")) {
+			panic!("{}", e);
+		}
+	}
+
+	#[test]
+	fn invalid_values_name_their_key() {
+		let cases = [
+			("timestep = 0.0", "timestep: must be greater than 0"),
+			("[scene.physics]\nstep_count = 0", "scene.physics.step_count: must be at least 1"),
+			("[scene]\npalette = [\"#ff8000\", \"orange\"]", "scene.palette[1]: expected a colour like \"#ff8000\""),
+			("[[scene.post]]\neffect = \"bloom\"\nthreshold = 2.0", "scene.post[0].threshold: must be between 0 and 1"),
+			("[[scene.colliders]]\nshape = \"circle\"\ncenter = [0.5, 0.5]\nradius = 0.0", "scene.colliders[0].radius: must be greater than 0"),
+			("[[monitor]]\nindex = 0\n[[monitor]]\nscene = {}", "monitor[1]: needs a name or an index"),
+			("[[monitor]]\nindex = 0\n[[monitor.scene.emitters]]\n[[monitor.scene.emitters]]\nrate = 0.0", "monitor[0].scene.emitters[1].rate: must be greater than 0"),
+			("[[monitor]]\nname = \"DP-1\"\n[[monitor.scene.ropes]]\nsegments = 0", "monitor[0].scene.ropes[0].segments: must be at least 1")
+		];
+		for (text, expected) in cases {
+			assert_eq!(error(text), expected);
+		}
+	}
+
+	#[test]
+	fn misspelled_keys_are_rejected() {
+		for (text, key) in [("timestpe = 0.01", "timestpe"), ("[scene.physics]\nstep_cout = 4", "step_cout"), ("[[scene.emitters]]\nrat = 5.0", "rat")] {
+			let e = error(text);
+			assert!(e.contains(&format!("unknown field `{}`", key)), "{}", e);
+		}
+	}
+}
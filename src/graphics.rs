@@ -42,7 +42,7 @@ pub struct Rect {
 	index_buffer: GLuint
 }
 
-fn map<T: std::ops::Add<Output=T> + std::ops::Sub<Output=T> + std::ops::Div<Output=T> + std::ops::Mul<Output=T> + Copy>(start1: T, end1: T, start2: T, end2: T, value: T) -> T {
+pub fn map<T: std::ops::Add<Output=T> + std::ops::Sub<Output=T> + std::ops::Div<Output=T> + std::ops::Mul<Output=T> + Copy>(start1: T, end1: T, start2: T, end2: T, value: T) -> T {
 	((value - start1) / (end1 - start1)) * (end2 - start2) + start2
 }
 
@@ -104,6 +104,15 @@ impl Rect {
 	}
 }
 
+impl Drop for Rect {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteBuffers(1, &self.vertex_buffer);
+			gl::DeleteBuffers(1, &self.index_buffer);
+		}
+	}
+}
+
 pub mod shader {
 
 	extern crate nalgebra_glm as glm;
@@ -125,6 +134,26 @@ pub mod shader {
                 unsafe { gl::GetUniformLocation(self.gl_id, name.as_ptr() as *const GLchar) };
         }*/
 
+        pub fn set_uniform1i(&self, name: &str, x: i32) {
+            let name = CString::new(name).unwrap();
+            unsafe { gl::Uniform1i(gl::GetUniformLocation(self.gl_id, name.as_ptr()), x) };
+        }
+
+        pub fn set_uniform1f(&self, name: &str, x: f32) {
+            let name = CString::new(name).unwrap();
+            unsafe { gl::Uniform1f(gl::GetUniformLocation(self.gl_id, name.as_ptr()), x) };
+        }
+
+        pub fn set_uniform2f(&self, name: &str, x: f32, y: f32) {
+            let name = CString::new(name).unwrap();
+            unsafe { gl::Uniform2f(gl::GetUniformLocation(self.gl_id, name.as_ptr()), x, y) };
+        }
+
+        pub fn set_uniform4f(&self, name: &str, x: f32, y: f32, z: f32, w: f32) {
+            let name = CString::new(name).unwrap();
+            unsafe { gl::Uniform4f(gl::GetUniformLocation(self.gl_id, name.as_ptr()), x, y, z, w) };
+        }
+
 		#[allow(dead_code)]
         pub fn set_uniform4fv(&self, name: &str, data: &glm::Mat4) {
             let location =
@@ -141,10 +170,15 @@ pub mod shader {
     }
 
     pub fn load(shader_name: &str) -> Shader {
+        load_with(shader_name, shader_name)
+    }
+
+    // for fragment shaders that share another shader's vertex shader
+    pub fn load_with(vertex_name: &str, fragment_name: &str) -> Shader {
         let vertex_shader_source: String =
-            std::fs::read_to_string(format!("shaders/{}.vert", shader_name)).unwrap();
+            std::fs::read_to_string(format!("shaders/{}.vert", vertex_name)).unwrap();
         let fragment_shader_source: String =
-            std::fs::read_to_string(format!("shaders/{}.frag", shader_name)).unwrap();
+            std::fs::read_to_string(format!("shaders/{}.frag", fragment_name)).unwrap();
 
         // Compile and link the shaders
         let vertex_shader = compile_shader(&vertex_shader_source[..], gl::VERTEX_SHADER);
@@ -156,6 +190,12 @@ pub mod shader {
         }
     }
 
+    impl Drop for Shader {
+        fn drop(&mut self) {
+            unsafe { gl::DeleteProgram(self.gl_id) };
+        }
+    }
+
     fn compile_shader(source: &str, shader_type: GLenum) -> GLuint {
         unsafe {
             let shader = gl::CreateShader(shader_type);
@@ -224,6 +264,8 @@ pub mod shader {
     }
 }
 
+// uploaded to textures and instance buffers as four bytes in rgba order
+#[repr(C)]
 #[derive(Copy)]
 pub struct Color {
 	pub r: u8,
@@ -232,6 +274,10 @@ pub struct Color {
 	pub a: u8
 }
 
+impl Color {
+	pub const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+}
+
 impl Clone for Color {
 	fn clone(&self) -> Self {
 		Color {
@@ -243,6 +289,81 @@ impl Clone for Color {
 	}
 }
 
+// one particle, laid out the way the circle shader reads its instance attributes
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Circle {
+	pub x: f32,
+	pub y: f32,
+	pub radius: f32,
+	pub color: Color
+}
+
+// a quad drawn once per circle, the fragment shader cuts the circle out of it
+pub struct CircleBuffer {
+	quad_buffer: GLuint,
+	instance_buffer: GLuint
+}
+
+impl CircleBuffer {
+	pub fn new() -> CircleBuffer {
+		let corners: [GLfloat; 4 * 2] = [
+			-1.0, -1.0,
+			1.0, -1.0,
+			1.0, 1.0,
+			-1.0, 1.0
+		];
+		let mut buffers: [GLuint; 2] = [0, 0];
+		unsafe {
+			gl::GenBuffers(2, buffers.as_mut_ptr());
+			gl::BindBuffer(gl::ARRAY_BUFFER, buffers[0]);
+			gl::BufferData(gl::ARRAY_BUFFER, std::mem::size_of_val(&corners) as isize, corners.as_ptr() as *const _, gl::STATIC_DRAW);
+		}
+		CircleBuffer {
+			quad_buffer: buffers[0],
+			instance_buffer: buffers[1]
+		}
+	}
+
+	pub fn draw(&self, circles: &[Circle]) {
+		if circles.is_empty() {
+			return;
+		}
+		unsafe {
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.quad_buffer);
+			gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 2 * std::mem::size_of::<GLfloat>() as GLsizei, std::ptr::null());
+			gl::EnableVertexAttribArray(0);
+
+			// orphan last frame's data instead of waiting for the GPU to finish with it
+			let stride = std::mem::size_of::<Circle>() as GLsizei;
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.instance_buffer);
+			gl::BufferData(gl::ARRAY_BUFFER, std::mem::size_of_val(circles) as isize, circles.as_ptr() as *const _, gl::STREAM_DRAW);
+			gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, stride, std::ptr::null());
+			gl::EnableVertexAttribArray(1);
+			gl::VertexAttribDivisor(1, 1);
+			gl::VertexAttribPointer(2, 4, gl::UNSIGNED_BYTE, gl::TRUE, stride, (3 * std::mem::size_of::<GLfloat>()) as *const _);
+			gl::EnableVertexAttribArray(2);
+			gl::VertexAttribDivisor(2, 1);
+
+			gl::DrawArraysInstanced(gl::TRIANGLE_FAN, 0, 4, circles.len() as GLsizei);
+
+			// Rect shares the default vertex array and doesn't expect instanced attributes
+			gl::VertexAttribDivisor(1, 0);
+			gl::VertexAttribDivisor(2, 0);
+			gl::DisableVertexAttribArray(2);
+		}
+	}
+}
+
+impl Drop for CircleBuffer {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteBuffers(1, &self.quad_buffer);
+			gl::DeleteBuffers(1, &self.instance_buffer);
+		}
+	}
+}
+
 pub struct Texture {
 	pub width: i32,
 	pub height: i32,
@@ -301,13 +422,50 @@ impl Texture {
     }
 }
 
-#[allow(dead_code)]
+impl Drop for Texture {
+	fn drop(&mut self) {
+		// 0 is the default frame buffer's, deleting it does nothing
+		unsafe { gl::DeleteTextures(1, &self.gl_id) };
+	}
+}
+
+// a 3D colour lookup table, red along x, green along y and blue along z
+pub struct LutTexture {
+	gl_id: GLuint
+}
+
+impl LutTexture {
+	pub fn new(size: i32, data: &[[f32; 3]]) -> LutTexture {
+		let mut gl_id: GLuint = 0;
+		unsafe {
+			gl::GenTextures(1, &mut gl_id);
+			gl::BindTexture(gl::TEXTURE_3D, gl_id);
+			gl::TexImage3D(gl::TEXTURE_3D, 0, gl::RGB32F as i32, size, size, size, 0, gl::RGB, gl::FLOAT, data.as_ptr() as *const _);
+			gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+			gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+			gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+			gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+			gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+		}
+		LutTexture { gl_id }
+	}
+
+	pub fn bind(&self) {
+		unsafe { gl::BindTexture(gl::TEXTURE_3D, self.gl_id) };
+	}
+}
+
+impl Drop for LutTexture {
+	fn drop(&mut self) {
+		unsafe { gl::DeleteTextures(1, &self.gl_id) };
+	}
+}
+
 pub struct FrameBuffer {
 	pub texture: Texture,
     gl_id: GLuint
 }
 
-#[allow(dead_code)]
 impl FrameBuffer {
     pub fn bind(&self) {
         unsafe {
@@ -326,24 +484,34 @@ impl FrameBuffer {
 		let texture: Texture = Texture::new(width, height);
 
         unsafe {
+            // passes that sample around a pixel shouldn't wrap to the other side
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+
             gl::FramebufferTexture(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, texture.gl_id, 0);
 
             let draw_buffers: [GLenum; 1] = [gl::COLOR_ATTACHMENT0];
             gl::DrawBuffers(1, draw_buffers.as_ptr());
         }
 
+        // dropped on failure, which deletes it again
+        let frame_buffer = FrameBuffer {
+            gl_id: frame_buffer_name,
+            texture
+        };
+
         unsafe {
-            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+            let complete = gl::CheckFramebufferStatus(gl::FRAMEBUFFER) == gl::FRAMEBUFFER_COMPLETE;
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            if !complete {
                 return Err("Failed to create frame buffer.".to_owned());
             }
         }
 
-        Ok(FrameBuffer {
-            gl_id: frame_buffer_name,
-            texture: texture
-        })
+        Ok(frame_buffer)
     }
 
+    #[allow(dead_code)]
     pub fn default(width: i32, height: i32) -> FrameBuffer {
         FrameBuffer {
             gl_id: 0,
@@ -355,3 +523,12 @@ impl FrameBuffer {
         }
     }
 }
+
+impl Drop for FrameBuffer {
+	fn drop(&mut self) {
+		// the default frame buffer isn't ours to delete
+		if self.gl_id != 0 {
+			unsafe { gl::DeleteFramebuffers(1, &self.gl_id) };
+		}
+	}
+}
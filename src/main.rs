@@ -1,82 +1,442 @@
 extern crate gl;
-extern crate gl_loader;
 extern crate nalgebra_glm as glm;
 extern crate rand;
 
 mod graphics;
+#[cfg(windows)]
 mod window;
+#[cfg(all(unix, feature = "x11"))]
+mod x11;
+#[cfg(all(unix, feature = "wayland"))]
+mod wayland;
 mod monitor;
+mod platform;
 mod physics;
 mod array2d;
+mod bench;
+mod render;
+mod config;
+mod scene;
+mod cli;
+mod timestep;
+mod topology;
+mod unified;
+mod post;
 
-use array2d::*;
-use graphics::*;
-use window::*;
 use monitor::*;
+use platform::*;
 use physics::*;
+use render::*;
+use config::{Config, FillArea, Layout, PauseAction};
+use cli::*;
+use timestep::FixedTimestep;
+use topology::Topology;
+use unified::Unified;
+
+use clap::Parser;
 
 fn main()
 {
-	let monitors: Vec<Monitor> = monitors_list();
+	let cli = Cli::parse();
+	let result = match cli.command.unwrap_or(Command::Run(RunArgs::default())) {
+		Command::Run(args) => run(args),
+		Command::ListMonitors(args) => list_monitors(args),
+		Command::Bench(args) => bench(args),
+		Command::Render(args) => render(args)
+	};
+	if let Err(e) = result {
+		eprintln!("{}", e);
+		std::process::exit(1);
+	}
+}
+
+fn load_config(args: &SceneArgs) -> Result<Config, String> {
+	let mut config = match args.config.as_ref() {
+		Some(path) if !path.exists() => return Err(format!("Failed to read {}: file not found", path.display())),
+		Some(path) => Config::load(path)?,
+		None => {
+			let path = std::env::var_os("DESKTOP_SIM_CONFIG").map_or(std::path::PathBuf::from(config::DEFAULT_PATH), std::path::PathBuf::from);
+			Config::load(&path)?
+		}
+	};
+	if let Some(scale) = args.scale {
+		config.scene.downscale = scale as usize;
+		for monitor in config.monitors.iter_mut() {
+			monitor.scene.downscale = scale as usize;
+		}
+	}
+	// picked once so monitors rebuilt later get the same scene again
+	config.seed = Some(args.seed.or(config.seed).unwrap_or_else(rand::random));
+	Ok(config)
+}
+
+fn is_selected(selection: &[String], index: usize, monitor: &Monitor) -> bool {
+	selection.is_empty() || selection.iter().any(|key| &monitor.name == key || key.parse::<usize>() == Ok(index))
+}
+
+// the monitor cut down to the area its scene fills
+fn scene_area(config: &Config, index: usize, monitor: &Monitor) -> Monitor {
+	match config.scene_for(index, monitor).area {
+		FillArea::Full => monitor.clone(),
+		FillArea::Work => monitor.work_area()
+	}
+}
+
+// keeps the monitors picked with --monitor, paired with their index in the full list and cut down to their scene's area
+fn select_monitors(config: &Config, monitors: Vec<Monitor>, selection: &[String]) -> Result<Vec<(usize, Monitor)>, String> {
+	let monitors: Vec<(usize, Monitor)> = monitors.into_iter().enumerate().collect();
+	if let Some(key) = selection.iter().find(|key| !monitors.iter().any(|(index, monitor)| is_selected(std::slice::from_ref(*key), *index, monitor))) {
+		return Err(format!("No monitor named or numbered {}", key));
+	}
+	Ok(monitors.into_iter()
+		.filter(|(index, monitor)| is_selected(selection, *index, monitor))
+		.map(|(index, monitor)| (index, scene_area(config, index, &monitor)))
+		.collect())
+}
+
+// every monitor gets its own stream so identical monitors don't mirror each other
+fn scene_seed(config: &Config, index: usize) -> u64 {
+	config.seed.unwrap_or(0).wrapping_add(index as u64)
+}
+
+fn build_simulation(config: &Config, index: usize, monitor: &Monitor) -> (Physics, MonitorFrame) {
+	let scene = config.scene_for(index, monitor);
+	let simulation = scene::build(scene, monitor, scene_seed(config, index));
+	println!("num_rows: {}, num_collumns: {}", simulation.size.y as usize, simulation.size.x as usize);
+	let frame = MonitorFrame::new(scene.draw_mode(), simulation.size.x as usize, simulation.size.y as usize);
+	(simulation, frame)
+}
+
+// a simulation and frame per monitor, or with the unified layout one simulation and a frame per monitor
+fn build_simulations(config: &Config, monitors: &[(usize, Monitor)], threads: usize) -> (Vec<Physics>, Vec<MonitorFrame>, Option<Unified>) {
+	if config.layout == Layout::Unified {
+		let monitor_list: Vec<Monitor> = monitors.iter().map(|(_, monitor)| monitor.clone()).collect();
+		let (mut simulation, unified) = unified::build(&config.scene, &monitor_list, scene_seed(config, 0));
+		simulation.config.threads = threads;
+		let monitor_frames = unified.frames(&monitor_list);
+		return (vec![simulation], monitor_frames, Some(unified));
+	}
+
+	let mut monitor_frames: Vec<MonitorFrame> = Vec::<MonitorFrame>::with_capacity(monitors.len());
+	let mut simulations: Vec<Physics> = Vec::<Physics>::with_capacity(monitors.len());
+	for (index, monitor) in monitors.iter() {
+		let (simulation, frame) = build_simulation(config, *index, monitor);
+		monitor_frames.push(frame);
+		simulations.push(simulation);
+	}
+	share_threads(&mut simulations, threads);
+	(simulations, monitor_frames, None)
+}
+
+fn render_simulations(simulations: &[Physics], unified: Option<&mut Unified>, monitors: &[Monitor], monitor_frames: &mut [MonitorFrame], alpha: f32) {
+	match unified {
+		Some(unified) => unified.render(&simulations[0], alpha, monitors, monitor_frames),
+		None => {
+			for (simulation, frame) in simulations.iter().zip(monitor_frames.iter_mut()) {
+				frame.fill(simulation, alpha);
+			}
+		}
+	}
+}
+
+fn share_threads(simulations: &mut [Physics], threads: usize) {
+	let count = simulations.len().max(1);
+	for simulation in simulations.iter_mut() {
+		simulation.config.threads = (threads / count).max(1);
+	}
+}
+
+// an empty topology keeps every monitor to itself, the unified layout doesn't need one
+fn connect_monitors(config: &Config, monitors: &[Monitor], simulations: &mut [Physics]) -> Topology {
+	let connect = config.connect_monitors && config.layout == Layout::Separate;
+	let topology = Topology::new(if connect { monitors } else { &[] });
+	topology.open_edges(monitors, simulations);
+	topology
+}
+
+// the effects of every monitor's scene, the unified layout draws all of them with [scene]
+fn monitor_effects(config: &Config, monitors: &[(usize, Monitor)]) -> Result<Vec<Effects>, String> {
+	monitors.iter().map(|(index, monitor)| match config.layout {
+		Layout::Separate => config.scene_for(*index, monitor).effects(),
+		Layout::Unified => config.scene.effects()
+	}).collect()
+}
+
+fn create_renderer(platform: &dyn Platform, surface: &Surface, monitors: &[Monitor], monitor_frames: &[MonitorFrame], effects: &[Effects], config: &Config) -> Result<Box<dyn Renderer>, String> {
+	if platform.has_gl() {
+		Ok(Box::new(GlRenderer::new(surface, monitors, monitor_frames, effects, config.clear_color())?))
+	} else {
+		Ok(Box::new(CpuRenderer::new(surface, monitors, effects, config.clear_color(), None, config.frame_interval)?))
+	}
+}
+
+fn draw_frame(platform: &mut dyn Platform, renderer: &mut dyn Renderer, monitors: &[Monitor], monitor_frames: &mut [MonitorFrame], dt: f32) -> Result<(), String> {
+	renderer.begin_frame(dt);
+	for (i, (monitor, frame)) in monitors.iter().zip(monitor_frames.iter_mut()).enumerate() {
+		if platform.begin_output(monitor) {
+			match frame {
+				MonitorFrame::Texture(texture_data) => renderer.draw(i, texture_data),
+				MonitorFrame::Circles { circles, size } => renderer.draw_circles(i, circles, *size)
+			}
+		}
+		frame.clear();
+	}
+	renderer.end_frame()?;
+	platform.present();
+	Ok(())
+}
+
+fn list_monitors(args: ListMonitorsArgs) -> Result<(), String> {
+	let mut platform = platform::native()?;
+	let monitors = platform.monitors();
+	platform.destroy();
+
+	if args.json {
+		let list: Vec<serde_json::Value> = monitors.iter().enumerate().map(|(index, monitor)| serde_json::json!({
+			"index": index,
+			"name": monitor.name,
+			"x": monitor.x,
+			"y": monitor.y,
+			"width": monitor.w,
+			"height": monitor.h,
+			"work_area": {
+				"x": monitor.work.x,
+				"y": monitor.work.y,
+				"width": monitor.work.w,
+				"height": monitor.work.h
+			},
+			"primary": monitor.primary,
+			"scale": monitor.scale,
+			"refresh_rate": monitor.refresh_rate
+		})).collect();
+		println!("{}", serde_json::to_string_pretty(&list).unwrap());
+	} else {
+		println!("{:>5}  {:<24} {:>6} {:>6} {:>6} {:>6}  {:<22} {:>7} {:>5} {:>7}", "index", "name", "x", "y", "width", "height", "work area", "primary", "scale", "refresh");
+		for (index, monitor) in monitors.iter().enumerate() {
+			let work = format!("{},{} {}x{}", monitor.work.x, monitor.work.y, monitor.work.w, monitor.work.h);
+			let primary = if monitor.primary { "yes" } else { "" };
+			println!("{:>5}  {:<24} {:>6} {:>6} {:>6} {:>6}  {:<22} {:>7} {:>5.2} {:>5.1}Hz", index, monitor.name, monitor.x, monitor.y, monitor.w, monitor.h, work, primary, monitor.scale, monitor.refresh_rate);
+		}
+	}
+	Ok(())
+}
+
+fn bench(args: BenchArgs) -> Result<(), String> {
+	if args.micro {
+		bench::collisions(&[500, 1000, 2000, 4000, 8000]);
+		bench::materials();
+		bench::threads(8000, &[1, 2, 4, 8]);
+		return Ok(());
+	}
+
+	let config = load_config(&args.scene)?;
+	let monitors = select_monitors(&config, HeadlessPlatform::default().monitors(), &args.scene.monitor)?;
+	let threads: usize = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+	let (mut simulations, _, _) = build_simulations(&config, &monitors, threads);
+
+	let mut times: Vec<f64> = Vec::with_capacity(args.steps);
+	for _ in 0..args.steps {
+		let start = std::time::Instant::now();
+		update_all(&mut simulations, config.timestep, threads);
+		times.push((std::time::Instant::now() - start).as_secs_f64() * 1000.0);
+	}
+	if times.is_empty() {
+		return Ok(());
+	}
+
+	let objects: usize = simulations.iter().map(|simulation| simulation.object_count()).sum();
+	let average = times.iter().sum::<f64>() / times.len() as f64;
+	times.sort_by(|a, b| a.total_cmp(b));
+	let p95 = times[(times.len() * 95 / 100).min(times.len() - 1)];
+	println!("steps: {}, objects: {}, update min: {:.3}ms, average: {:.3}ms, p95: {:.3}ms, max: {:.3}ms", times.len(), objects, times[0], average, p95, times[times.len() - 1]);
+	Ok(())
+}
+
+fn render(args: RenderArgs) -> Result<(), String> {
+	let config = load_config(&args.scene)?;
+	let mut platform = HeadlessPlatform::new(vec![Monitor::new("headless", 0, 0, args.width, args.height)]);
+	let monitors = select_monitors(&config, platform.monitors(), &args.scene.monitor)?;
+	let surface: Surface = platform.create_surface()?;
+	let threads: usize = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+	let (mut simulations, mut monitor_frames, mut unified) = build_simulations(&config, &monitors, threads);
+
+	let effects = monitor_effects(&config, &monitors)?;
+	let monitor_list: Vec<Monitor> = monitors.into_iter().map(|(_, monitor)| monitor).collect();
+	let every = args.every.unwrap_or(config.frame_interval).max(1);
+	let mut renderer = CpuRenderer::new(&surface, &monitor_list, &effects, config.clear_color(), Some(args.output.clone()), every)?;
+
+	// fixed steps as fast as possible, no need to keep up with the clock
+	for _ in 0..(args.frames * every) {
+		update_all(&mut simulations, config.timestep, threads);
+		render_simulations(&simulations, unified.as_mut(), &monitor_list, &mut monitor_frames, 1.0);
+		draw_frame(&mut platform, &mut renderer, &monitor_list, &mut monitor_frames, config.timestep)?;
+	}
+	println!("Wrote {} frames to {}", args.frames, args.output.display());
+	Ok(())
+}
+
+fn run(args: RunArgs) -> Result<(), String> {
+	let mut config = load_config(&args.scene)?;
+	if let Some(duration) = args.duration {
+		config.duration = duration;
+	}
+	if args.fps_cap.is_some() {
+		config.fps_cap = args.fps_cap;
+	}
+
+	let mut platform: Box<dyn Platform> = if args.headless {
+		let mut headless = HeadlessPlatform::default();
+		for state in args.fake_state.iter() {
+			match state {
+				FakeState::Obscured => headless.desktop_state.obscured = true,
+				FakeState::Locked => headless.desktop_state.locked = true,
+				FakeState::OnBattery => headless.desktop_state.on_battery = true,
+				FakeState::DisplayOff => headless.desktop_state.display_off = true
+			}
+		}
+		Box::new(headless)
+	} else {
+		platform::native()?
+	};
+
+	let mut monitors = select_monitors(&config, platform.monitors(), &args.scene.monitor)?;
 	println!("Found {} monitors.", monitors.len());
-	for monitor in monitors.iter() {
-		println!("pos: ({}, {}), size: ({}, {})", monitor.x, monitor.y, monitor.w, monitor.h);
+	for (_, monitor) in monitors.iter() {
+		println!("{}: pos: ({}, {}), size: ({}, {})", monitor.name, monitor.x, monitor.y, monitor.w, monitor.h);
 	}
 
-	let window: WindowInfo = window_background_create().unwrap();
+	let mut surface: Surface = platform.create_surface()?;
 
 	//let frame_buffer_default: FrameBuffer = FrameBuffer::default(window.width, window.height);
-	let mut textures: Vec<Texture> = Vec::<Texture>::with_capacity(monitors.len());
-	let mut textures_data: Vec<Array2D<Color>> = Vec::<Array2D<Color>>::with_capacity(monitors.len());
-	let mut simulations: Vec<Physics> = Vec::<Physics>::with_capacity(monitors.len());
-	let mut rectangles: Vec<Rect> = Vec::<Rect>::with_capacity(monitors.len());
-	for monitor in monitors.iter() {
-		textures.push(Texture::new(monitor.w / 4 / 4, monitor.h / 4 / 4));
-		println!("num_rows: {}, num_collumns: {}", monitor.h as usize, monitor.w as usize);
-		let default_color = Color { r: 0 as u8, g: 0 as u8, b: 0 as u8, a: 255 as u8 };
-		let texture_data = Array2D::new(monitor.w as usize / 4 / 4, monitor.h as usize / 4 / 4, default_color);
-		textures_data.push(texture_data);
-		simulations.push(Physics::new(monitor.w as usize / 4 / 4, monitor.h as usize / 4 / 4));
-		rectangles.push(Rect::new(monitor.x as f32, monitor.y as f32, monitor.w as f32, monitor.h as f32, window.x as f32, window.y as f32, window.width as f32, window.height as f32));
-	}
-
-	let shader: graphics::shader::Shader = graphics::shader::load("texture");
-	shader.bind();
-
-	//let shader_circle: graphics::shader::Shader = graphics::shader::load("circle");
-	
-	
-	unsafe { gl::ClearColor(0.1, 0.1, 0.2, 1.0) };
-
-	use rand::Rng;
-	let mut color = Color { r: rand::thread_rng().gen_range(0..=255), g: rand::thread_rng().gen_range(0..=255), b: rand::thread_rng().gen_range(0..=255), a: 255 };
-	let mut red_change: i32 = 1;
-	let mut green_change: i32 = -1;
-	let mut blue_change: i32 = 1;
+	let threads: usize = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+	let (mut simulations, mut monitor_frames, mut unified) = build_simulations(&config, &monitors, threads);
+
+	let mut monitor_list: Vec<Monitor> = monitors.iter().map(|(_, monitor)| monitor.clone()).collect();
+	let mut renderer: Box<dyn Renderer> = create_renderer(platform.as_ref(), &surface, &monitor_list, &monitor_frames, &monitor_effects(&config, &monitors)?, &config)?;
+	let mut topology = connect_monitors(&config, &monitor_list, &mut simulations);
 
+	let mut timestep = FixedTimestep::new(config.timestep, config.max_updates_per_frame, config.fps_cap);
+	let mut frame_start = std::time::Instant::now();
 	let mut total_duration: f32 = 0.0;
 	let mut duration: f32 = 0.0;
-	let mut last_time = std::time::Instant::now();
 	let mut frames = 0;
 	let mut updates = 0;
 
 	let mut average_update_time: u128 = 0;
 	let mut total_update_count: u32 = 0;
 
-	while total_duration < 1000.0 {
+	let mut pause_action = PauseAction::Run;
+	let mut last_state_check: Option<std::time::Instant> = None;
 
-		// timing
-		let time = std::time::Instant::now();
+	while config.duration == 0.0 || total_duration < config.duration {
 
-		let dt = (time - last_time).as_secs_f32();
+		// events
+		let mut quit = false;
+		let mut layout_changed = false;
+		for event in platform.poll_events() {
+			match event {
+				Event::Quit => quit = true,
+				Event::MonitorAdded(monitor) => {
+					if args.verbose {
+						println!("monitor added: {}, pos: ({}, {}), size: ({}, {})", monitor.name, monitor.x, monitor.y, monitor.w, monitor.h);
+					}
+					let index = platform.monitors().iter().position(|other| other.name == monitor.name).unwrap_or(monitors.len());
+					if is_selected(&args.scene.monitor, index, &monitor) && !monitors.iter().any(|(_, other)| other.name == monitor.name) {
+						let monitor = scene_area(&config, index, &monitor);
+						if unified.is_none() {
+							let (simulation, frame) = build_simulation(&config, index, &monitor);
+							simulations.push(simulation);
+							monitor_frames.push(frame);
+						}
+						monitors.push((index, monitor));
+						layout_changed = true;
+					}
+				},
+				Event::MonitorRemoved(monitor) => {
+					if args.verbose {
+						println!("monitor removed: {}", monitor.name);
+					}
+					if let Some(i) = monitors.iter().position(|(_, other)| other.name == monitor.name) {
+						monitors.remove(i);
+						if unified.is_none() {
+							simulations.remove(i);
+							monitor_frames.remove(i);
+						}
+						layout_changed = true;
+					}
+				},
+				Event::MonitorChanged(monitor) => {
+					if args.verbose {
+						println!("monitor changed: {}, pos: ({}, {}), size: ({}, {})", monitor.name, monitor.x, monitor.y, monitor.w, monitor.h);
+					}
+					if let Some(i) = monitors.iter().position(|(_, other)| other.name == monitor.name) {
+						// keep the particles, only the grid and frame change size
+						let monitor = scene_area(&config, monitors[i].0, &monitor);
+						if unified.is_none() {
+							let scene = config.scene_for(monitors[i].0, &monitor);
+							let (w, h) = scene::size(scene, &monitor);
+							simulations[i].resize(w, h, config.resize_mode());
+							monitor_frames[i] = MonitorFrame::new(scene.draw_mode(), w, h);
+						}
+						monitors[i].1 = monitor;
+						layout_changed = true;
+					}
+				},
+				Event::SurfaceChanged(new_surface) => {
+					surface = new_surface;
+					layout_changed = true;
+				}
+			}
+		}
+		if quit {
+			break;
+		}
+		if layout_changed {
+			share_threads(&mut simulations, threads);
+			monitor_list = monitors.iter().map(|(_, monitor)| monitor.clone()).collect();
+			if let Some(unified) = unified.as_mut() {
+				unified.relayout(&mut simulations[0], &config.scene, &monitor_list, config.resize_mode());
+				monitor_frames = unified.frames(&monitor_list);
+			}
+			renderer = create_renderer(platform.as_ref(), &surface, &monitor_list, &monitor_frames, &monitor_effects(&config, &monitors)?, &config)?;
+			topology = connect_monitors(&config, &monitor_list, &mut simulations);
+		}
+
+		// pause or throttle while nobody can see the desktop
+		if last_state_check.is_none_or(|time| time.elapsed().as_secs_f32() >= config.pause.check_interval) {
+			let state = platform.desktop_state();
+			let action = config.pause.action(state);
+			if action != pause_action {
+				if args.verbose {
+					println!("desktop state: {:?}, {:?}", state, action);
+				}
+				let throttled = action == PauseAction::Throttle;
+				timestep.fps_cap = if throttled { Some(config.pause.throttle_fps) } else { config.fps_cap }.filter(|fps_cap| *fps_cap > 0.0);
+				timestep.max_updates = if throttled { 1 } else { config.max_updates_per_frame };
+				pause_action = action;
+			}
+			last_state_check = Some(std::time::Instant::now());
+		}
+		if pause_action == PauseAction::Pause {
+			let pause_start = std::time::Instant::now();
+			std::thread::sleep(std::time::Duration::from_secs_f32(config.pause.check_interval.min(0.1)));
+			total_duration += pause_start.elapsed().as_secs_f32();
+			timestep.reset();
+			frame_start = std::time::Instant::now();
+			continue;
+		}
+
+		// timing
+		let now = std::time::Instant::now();
+		let update_count = timestep.begin_frame(now - frame_start);
+		frame_start = now;
+		let dt = timestep.frame_time();
 		duration += dt;
 		total_duration += dt;
-		
-		last_time = time;
 
 		frames += 1;
 		if duration >= 1.0 {
-			let current_average_update_time = (average_update_time / total_update_count as u128) as f64 / 1000000.0;
+			let current_average_update_time = (average_update_time / total_update_count.max(1) as u128) as f64 / 1000000.0;
 			println!("fps: {}, ups: {}, average_update_time: {:.3}ms, calc_ups: {}", frames, updates, current_average_update_time, (1000.0 / current_average_update_time) as u128);
 			frames = 0;
 			updates = 0;
@@ -85,58 +445,43 @@ fn main()
 
 		// update
 		let update_start_time = std::time::Instant::now();
-		{
-			for i in 0..simulations.len() {
-				simulations[i].update(1.0 / 30.0);
-				simulations[i].render(textures_data.get_mut(i).unwrap());
-
-				if updates % 10 == 0 {
-					let x: f32 = simulations[i].size.x / 2.0;
-					let y: f32 = simulations[i].size.y / 2.0;
-					let index = simulations[i].add_object(x, y, color);
-					simulations[i][index].vel.x -= 0.2;
-
-					color.r = (color.r as i32 + red_change  ) as u8;
-					color.g = (color.g as i32 + green_change) as u8;
-					color.b = (color.b as i32 + blue_change ) as u8;
-
-					if color.r == 255 || color.r == 0 { red_change = -red_change; }
-					if color.g == 255 || color.g == 0 { green_change = -green_change; }
-					if color.b == 255 || color.b == 0 { blue_change = -blue_change; }
-				}
-			}
+		for _ in 0..update_count {
+			update_all(&mut simulations, config.timestep, threads);
+			topology.transfer(&monitor_list, &mut simulations);
+		}
+		if update_count > 0 {
+			average_update_time += (std::time::Instant::now() - update_start_time).as_nanos();
+			total_update_count += update_count as u32;
+			updates += update_count;
 		}
-		average_update_time += (std::time::Instant::now() - update_start_time).as_nanos();
-		total_update_count += 1;
-		updates += 1;
 
 		// render
-		{
-			unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) };
-			for i in 0..simulations.len() {
-				textures[i].set_data(&textures_data[i]);
-				rectangles[i].draw();
-
-				let c = Color { r: 0 as u8, g: 0 as u8, b: 0 as u8, a: 255 as u8 };
-				for y in 0..textures_data[i].height() {
-					for x in 0..textures_data[i].width() {
-						textures_data[i][(x, y)] = c;
-					}
-				}
-			}
-			window_swap_buffers(&window);
+		let alpha = if config.interpolate { timestep.alpha() } else { 1.0 };
+		render_simulations(&simulations, unified.as_mut(), &monitor_list, &mut monitor_frames, alpha);
+		draw_frame(platform.as_mut(), renderer.as_mut(), &monitor_list, &mut monitor_frames, dt)?;
+
+		let rest = timestep.sleep_time(frame_start.elapsed());
+		if !rest.is_zero() {
+			std::thread::sleep(rest);
 		}
 	}
 
-	println!("total_update_time: {}, total_update_count {}", average_update_time, total_update_count);
-	let current_average_update_time = (average_update_time / total_update_count as u128) as f64 / 1000000.0;
-	println!("average_update_time: {:.3}ms, calc_ups: {}", current_average_update_time, (1000.0 / current_average_update_time) as u128);
+	if total_update_count > 0 {
+		println!("total_update_time: {}, total_update_count {}", average_update_time, total_update_count);
+		let current_average_update_time = (average_update_time / total_update_count as u128) as f64 / 1000000.0;
+		println!("average_update_time: {:.3}ms, calc_ups: {}", current_average_update_time, (1000.0 / current_average_update_time) as u128);
+	}
 
-	unsafe { gl::ClearColor(0.4, 0.1, 0.1, 0.0) };
-	unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) };
-	window_swap_buffers(&window);
+	if platform.has_gl() {
+		unsafe { gl::ClearColor(0.4, 0.1, 0.1, 0.0) };
+		unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) };
+		platform.present();
+	}
 
-	window_background_destroy(window);
+	// the renderer's GL objects have to go before the context
+	drop(renderer);
+	platform.destroy();
+	Ok(())
 }
 
 
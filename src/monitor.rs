@@ -1,68 +1,135 @@
-extern crate winapi;
-
-use winapi::um::winuser::*;
-use winapi::shared::minwindef::*;
-use winapi::shared::windef::*;
-
-use std::io::Error;
-use std::mem;
-use std::ptr;
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MonitorRect {
+	pub x: i32,
+	pub y: i32,
+	pub w: i32,
+	pub h: i32
+}
 
+// x, y, w and h cover the whole monitor in desktop pixels
+#[derive(Clone, PartialEq)]
 pub struct Monitor {
 	pub name: String,
 	pub w: i32,
 	pub h: i32,
 	pub x: i32,
-	pub y: i32
+	pub y: i32,
+	// the monitor minus taskbars and docks, the whole monitor where the backend can't tell
+	pub work: MonitorRect,
+	pub primary: bool,
+	// desktop pixels per 96 dpi pixel
+	pub scale: f32,
+	// Hz, 0 when unknown
+	pub refresh_rate: f32
 }
 
-pub fn monitors_list(
-) -> Vec<Monitor> {
-	let mut monitors = Vec::<Monitor>::new();
-	let userdata = &mut monitors as *mut _;
-
-	let result = unsafe {
-		EnumDisplayMonitors(
-			ptr::null_mut(),
-			ptr::null(),
-			Some(enumerate_monitors_callback),
-			userdata as LPARAM
-		)
-	};
-
-	if result != TRUE {
-		// Get the last error for the current thread.
-		// This is analogous to calling the Win32 API GetLastError.
-		panic!("Could not enumerate monitors: {}", Error::last_os_error());
+impl Monitor {
+	pub fn new(name: &str, x: i32, y: i32, w: i32, h: i32) -> Monitor {
+		Monitor {
+			name: name.to_string(),
+			w,
+			h,
+			x,
+			y,
+			work: MonitorRect { x, y, w, h },
+			primary: false,
+			scale: 1.0,
+			refresh_rate: 0.0
+		}
 	}
 
-	monitors
+	// the same monitor shrunk to its work area
+	pub fn work_area(&self) -> Monitor {
+		Monitor {
+			w: self.work.w,
+			h: self.work.h,
+			x: self.work.x,
+			y: self.work.y,
+			..self.clone()
+		}
+	}
 }
 
-unsafe extern "system" fn enumerate_monitors_callback(
-	monitor: HMONITOR,
-	_: HDC,
-	_: LPRECT,
-	userdata: LPARAM,
-) -> BOOL {
-	let monitors: &mut Vec<Monitor> = mem::transmute(userdata);
-
-	let mut monitor_info: MONITORINFOEXW = mem::zeroed();
-	monitor_info.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
-	let monitor_info_ptr = <*mut _>::cast(&mut monitor_info);
-
-	let result = GetMonitorInfoW(monitor, monitor_info_ptr);
-	if result == TRUE {
-		let work_area: &RECT = &monitor_info.rcWork;
-		println!("left: {}, right: {}, top: {}, bottom: {}", work_area.left, work_area.right, work_area.top, work_area.bottom);
-		monitors.push(Monitor {
-			name: String::from_utf16(&monitor_info.szDevice).unwrap(),
-			w: (work_area.left - work_area.right).abs(),
-			h: (work_area.top - work_area.bottom).abs(),
-			x: work_area.left,
-			y: work_area.top
-		});
+#[cfg(windows)]
+pub use win32::monitors_list;
+
+#[cfg(windows)]
+mod win32 {
+	extern crate winapi;
+
+	use winapi::um::shellscalingapi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+	use winapi::um::wingdi::DEVMODEW;
+	use winapi::um::winuser::*;
+	use winapi::shared::minwindef::*;
+	use winapi::shared::windef::*;
+
+	use std::io::Error;
+	use std::mem;
+	use std::ptr;
+
+	use super::{Monitor, MonitorRect};
+
+	pub fn monitors_list(
+	) -> Vec<Monitor> {
+		let mut monitors = Vec::<Monitor>::new();
+		let userdata = &mut monitors as *mut _;
+
+		let result = unsafe {
+			EnumDisplayMonitors(
+				ptr::null_mut(),
+				ptr::null(),
+				Some(enumerate_monitors_callback),
+				userdata as LPARAM
+			)
+		};
+
+		if result != TRUE {
+			// Get the last error for the current thread.
+			// This is analogous to calling the Win32 API GetLastError.
+			panic!("Could not enumerate monitors: {}", Error::last_os_error());
+		}
+
+		monitors
 	}
 
-	TRUE
+	unsafe extern "system" fn enumerate_monitors_callback(
+		monitor: HMONITOR,
+		_: HDC,
+		_: LPRECT,
+		userdata: LPARAM,
+	) -> BOOL {
+		let monitors: &mut Vec<Monitor> = mem::transmute(userdata);
+
+		let mut monitor_info: MONITORINFOEXW = mem::zeroed();
+		monitor_info.cbSize = mem::size_of::<MONITORINFOEXW>() as u32;
+		let monitor_info_ptr = <*mut _>::cast(&mut monitor_info);
+
+		let result = GetMonitorInfoW(monitor, monitor_info_ptr);
+		if result == TRUE {
+			let full: &RECT = &monitor_info.rcMonitor;
+			let work_area: &RECT = &monitor_info.rcWork;
+			// szDevice is NUL padded
+			let length = monitor_info.szDevice.iter().position(|&c| c == 0).unwrap_or(monitor_info.szDevice.len());
+			let name = String::from_utf16_lossy(&monitor_info.szDevice[..length]);
+
+			let mut entry = Monitor::new(&name, full.left, full.top, full.right - full.left, full.bottom - full.top);
+			entry.work = MonitorRect { x: work_area.left, y: work_area.top, w: work_area.right - work_area.left, h: work_area.bottom - work_area.top };
+			entry.primary = monitor_info.dwFlags & MONITORINFOF_PRIMARY != 0;
+
+			let mut dpi_x: UINT = 0;
+			let mut dpi_y: UINT = 0;
+			if GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) == 0 && dpi_x > 0 {
+				entry.scale = dpi_x as f32 / 96.0;
+			}
+
+			let mut mode: DEVMODEW = mem::zeroed();
+			mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+			if EnumDisplaySettingsW(monitor_info.szDevice.as_ptr(), ENUM_CURRENT_SETTINGS, &mut mode) != 0 {
+				entry.refresh_rate = mode.dmDisplayFrequency as f32;
+			}
+			monitors.push(entry);
+		}
+
+		TRUE
+	}
 }
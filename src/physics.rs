@@ -1,26 +1,106 @@
-use crate::graphics::Color;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::graphics::{Circle, Color};
 use crate::array2d::Array2D;
 
+#[derive(Clone, Copy)]
+pub struct Material {
+	// fraction of the normal speed kept after an impact
+	pub restitution: f32,
+	// coulomb friction coefficient
+	pub friction: f32
+}
+
+impl Material {
+	pub const DEFAULT: Material = Material { restitution: 0.0, friction: 0.0 };
+	pub const SAND: Material = Material { restitution: 0.05, friction: 0.6 };
+	pub const BOUNCY: Material = Material { restitution: 0.9, friction: 0.05 };
+}
+
+// below this approach speed contacts don't bounce, so resting objects stay at rest
+const RESTING_SPEED: f32 = 1.0;
+
+// velocity after hitting a static surface with the given normal
+fn surface_response(vel: glm::Vec2, normal: glm::Vec2, material: Material) -> glm::Vec2 {
+	let normal_speed = glm::dot(&vel, &normal);
+	if normal_speed >= 0.0 {
+		return vel;
+	}
+
+	let restitution = if -normal_speed < RESTING_SPEED { 0.0 } else { material.restitution };
+	let normal_impulse = -(1.0 + restitution) * normal_speed;
+	let tangent = vel - normal * normal_speed;
+	let tangent_speed = glm::length(&tangent);
+
+	let mut new_vel = vel + normal * normal_impulse;
+	if tangent_speed > 0.0 {
+		let friction = (material.friction * normal_impulse).min(tangent_speed);
+		new_vel -= tangent / tangent_speed * friction;
+	}
+	new_vel
+}
+
 #[derive(Clone, Copy)]
 pub struct VerletObject {
 	pub pos: glm::Vec2,
+	// position before the last update, rendering interpolates from here
+	pub last_pos: glm::Vec2,
 	pub vel: glm::Vec2,
 	pub acc: glm::Vec2,
+	pub radius: f32,
+	pub mass: f32,
 	pub color: Color,
+	pub material: Material,
+	// seconds left before the object despawns
+	pub lifetime: Option<f32>,
+	alive: bool
 }
 
 impl VerletObject {
+	#[allow(dead_code)]
+	pub fn is_alive(&self) -> bool {
+		self.alive
+	}
+
 	fn check_collision(&mut self, other: &mut Self) {
 		let collision_axis = self.pos - other.pos;
 		let distance2 = glm::length2(&collision_axis);
-		if distance2 < 1.0 {
+		let min_distance = self.radius + other.radius;
+		if distance2 < min_distance * min_distance && distance2 > 0.0 {
 			let distance = distance2.sqrt();
 			let normal = collision_axis / distance;
-			let delta = 0.5 * (1.0 - distance);
-			self.pos = self.pos + normal * delta;
-			self.vel = self.vel + normal * delta  * 100.0;
-			other.pos = other.pos - normal * delta;
-			other.vel = other.vel - normal * delta * 100.0;
+			let overlap = min_distance - distance;
+
+			// the lighter object gets pushed further
+			let total_mass = self.mass + other.mass;
+			let delta = overlap * (other.mass / total_mass);
+			let other_delta = overlap * (self.mass / total_mass);
+			self.pos += normal * delta;
+			other.pos -= normal * other_delta;
+
+			// impulse along the normal only while the objects approach each other
+			let relative_vel = self.vel - other.vel;
+			let normal_speed = glm::dot(&relative_vel, &normal);
+			if normal_speed >= 0.0 {
+				return;
+			}
+			let restitution = if -normal_speed < RESTING_SPEED { 0.0 } else { self.material.restitution.min(other.material.restitution) };
+			let inv_mass = 1.0 / self.mass;
+			let other_inv_mass = 1.0 / other.mass;
+			let impulse = -(1.0 + restitution) * normal_speed / (inv_mass + other_inv_mass);
+			self.vel += normal * (impulse * inv_mass);
+			other.vel -= normal * (impulse * other_inv_mass);
+
+			let tangent = relative_vel - normal * normal_speed;
+			let tangent_speed = glm::length(&tangent);
+			if tangent_speed > 0.0 {
+				let friction = (self.material.friction * other.material.friction).sqrt();
+				let friction_impulse = (friction * impulse).min(tangent_speed / (inv_mass + other_inv_mass));
+				let direction = tangent / tangent_speed;
+				self.vel -= direction * (friction_impulse * inv_mass);
+				other.vel += direction * (friction_impulse * other_inv_mass);
+			}
 		}
 	}
 
@@ -34,105 +114,2105 @@ impl VerletObject {
 	}
 }
 
+const CELL_CAPACITY: usize = 7;
+const NO_CELL: u32 = u32::MAX;
+
 #[derive(Clone, Copy)]
 struct Cell {
 	count: u8,
-	indices: [u8; 7]
+	indices: [u32; CELL_CAPACITY],
+	next: u32
+}
+
+impl Cell {
+	const EMPTY: Cell = Cell { count: 0, indices: [0; CELL_CAPACITY], next: NO_CELL };
+
+	fn push(&mut self, index: u32) -> bool {
+		if (self.count as usize) < CELL_CAPACITY {
+			self.indices[self.count as usize] = index;
+			self.count += 1;
+			true
+		} else {
+			false
+		}
+	}
+}
+
+// uniform grid whose cells are as wide as the largest object, cells that run
+// out of slots chain into extra cells stored in `overflow`
+struct Grid {
+	cell_size: f32,
+	cells: Array2D<Cell>,
+	overflow: Vec<Cell>
+}
+
+impl Grid {
+	fn new(size: glm::Vec2, cell_size: f32) -> Grid {
+		let w = (size.x / cell_size).ceil() as usize;
+		let h = (size.y / cell_size).ceil() as usize;
+		Grid {
+			cell_size,
+			cells: Array2D::new(w.max(1), h.max(1), Cell::EMPTY),
+			overflow: Vec::new()
+		}
+	}
+
+	fn width(&self) -> usize {
+		self.cells.width()
+	}
+
+	fn height(&self) -> usize {
+		self.cells.height()
+	}
+
+	fn clear(&mut self) {
+		for cell in self.cells.as_mut_vec().iter_mut() {
+			cell.count = 0;
+			cell.next = NO_CELL;
+		}
+		self.overflow.clear();
+	}
+
+	fn cell_of(&self, pos: glm::Vec2) -> (usize, usize) {
+		let x = ((pos.x / self.cell_size).max(0.0) as usize).min(self.width() - 1);
+		let y = ((pos.y / self.cell_size).max(0.0) as usize).min(self.height() - 1);
+		(x, y)
+	}
+
+	fn insert(&mut self, x: usize, y: usize, index: u32) {
+		let head = &mut self.cells[(x, y)];
+		if head.push(index) {
+			return;
+		}
+		if head.next == NO_CELL {
+			head.next = self.overflow.len() as u32;
+			self.overflow.push(Cell::EMPTY);
+		}
+
+		let mut next = head.next as usize;
+		loop {
+			let new_index = self.overflow.len() as u32;
+			let cell = &mut self.overflow[next];
+			if cell.push(index) {
+				return;
+			}
+			if cell.next == NO_CELL {
+				cell.next = new_index;
+				self.overflow.push(Cell::EMPTY);
+			}
+			next = self.overflow[next].next as usize;
+		}
+	}
+
+	fn collect(&self, x: usize, y: usize, out: &mut Vec<u32>) {
+		let mut cell = &self.cells[(x, y)];
+		loop {
+			out.extend_from_slice(&cell.indices[..cell.count as usize]);
+			if cell.next == NO_CELL {
+				return;
+			}
+			cell = &self.overflow[cell.next as usize];
+		}
+	}
+}
+
+fn closest_point_on_segment(p: glm::Vec2, a: glm::Vec2, b: glm::Vec2) -> glm::Vec2 {
+	let ab = b - a;
+	let length2 = glm::length2(&ab);
+	if length2 == 0.0 {
+		return a;
+	}
+	let t = (glm::dot(&(p - a), &ab) / length2).clamp(0.0, 1.0);
+	a + ab * t
+}
+
+fn cross(a: glm::Vec2, b: glm::Vec2) -> f32 {
+	a.x * b.y - a.y * b.x
+}
+
+#[derive(Clone)]
+pub enum Collider {
+	Segment { a: glm::Vec2, b: glm::Vec2 },
+	Circle { center: glm::Vec2, radius: f32 },
+	Capsule { a: glm::Vec2, b: glm::Vec2, radius: f32 },
+	// convex, counter-clockwise
+	Polygon { points: Vec<glm::Vec2> }
+}
+
+impl Collider {
+	pub fn polygon(mut points: Vec<glm::Vec2>) -> Result<Collider, String> {
+		if points.len() < 3 {
+			return Err(format!("Polygon needs at least 3 points, got {}.", points.len()));
+		}
+
+		let mut positive = false;
+		let mut negative = false;
+		for i in 0..points.len() {
+			let a = points[i];
+			let b = points[(i + 1) % points.len()];
+			let c = points[(i + 2) % points.len()];
+			let turn = cross(b - a, c - b);
+			positive |= turn > 0.0;
+			negative |= turn < 0.0;
+		}
+		if positive && negative {
+			return Err("Polygon is not convex.".to_string());
+		}
+		if !positive && !negative {
+			return Err("Polygon has no area.".to_string());
+		}
+		if negative {
+			points.reverse();
+		}
+
+		Ok(Collider::Polygon { points })
+	}
+
+	// radii scale by the smaller factor so circles stay round
+	pub fn scale(&mut self, scale: glm::Vec2) {
+		let radius_scale = scale.x.min(scale.y);
+		match self {
+			Collider::Segment { a, b } => {
+				*a = a.component_mul(&scale);
+				*b = b.component_mul(&scale);
+			},
+			Collider::Circle { center, radius } => {
+				*center = center.component_mul(&scale);
+				*radius *= radius_scale;
+			},
+			Collider::Capsule { a, b, radius } => {
+				*a = a.component_mul(&scale);
+				*b = b.component_mul(&scale);
+				*radius *= radius_scale;
+			},
+			Collider::Polygon { points } => {
+				for point in points.iter_mut() {
+					*point = point.component_mul(&scale);
+				}
+			}
+		}
+	}
+
+	pub fn rectangle(min: glm::Vec2, max: glm::Vec2) -> Collider {
+		Collider::Polygon { points: vec![min, glm::vec2(max.x, min.y), max, glm::vec2(min.x, max.y)] }
+	}
+
+	pub fn bounds(&self) -> (glm::Vec2, glm::Vec2) {
+		match self {
+			Collider::Segment { a, b } => (glm::min2(a, b), glm::max2(a, b)),
+			Collider::Circle { center, radius } => (center - glm::vec2(*radius, *radius), center + glm::vec2(*radius, *radius)),
+			Collider::Capsule { a, b, radius } => (glm::min2(a, b) - glm::vec2(*radius, *radius), glm::max2(a, b) + glm::vec2(*radius, *radius)),
+			Collider::Polygon { points } => points.iter().fold((points[0], points[0]), |(min, max), p| (glm::min2(&min, p), glm::max2(&max, p)))
+		}
+	}
+
+	// signed distance from `p` to the surface and the direction pointing away from it
+	pub fn distance(&self, p: glm::Vec2) -> (f32, glm::Vec2) {
+		match self {
+			Collider::Segment { a, b } => Collider::segment_distance(p, *a, *b, 0.0),
+			Collider::Capsule { a, b, radius } => Collider::segment_distance(p, *a, *b, *radius),
+			Collider::Circle { center, radius } => {
+				let offset = p - center;
+				let distance = glm::length(&offset);
+				if distance == 0.0 {
+					(-radius, glm::vec2(0.0, 1.0))
+				} else {
+					(distance - radius, offset / distance)
+				}
+			},
+			Collider::Polygon { points } => {
+				let mut inside_distance = f32::NEG_INFINITY;
+				let mut inside_normal = glm::vec2(0.0, 1.0);
+				let mut outside_distance2 = f32::INFINITY;
+				let mut outside_point = p;
+				for i in 0..points.len() {
+					let a = points[i];
+					let b = points[(i + 1) % points.len()];
+					let edge = glm::normalize(&(b - a));
+					let normal = glm::vec2(edge.y, -edge.x);
+					let edge_distance = glm::dot(&(p - a), &normal);
+					if edge_distance > inside_distance {
+						inside_distance = edge_distance;
+						inside_normal = normal;
+					}
+					let closest = closest_point_on_segment(p, a, b);
+					let distance2 = glm::length2(&(p - closest));
+					if distance2 < outside_distance2 {
+						outside_distance2 = distance2;
+						outside_point = closest;
+					}
+				}
+
+				if inside_distance <= 0.0 {
+					(inside_distance, inside_normal)
+				} else {
+					let distance = outside_distance2.sqrt();
+					(distance, (p - outside_point) / distance)
+				}
+			}
+		}
+	}
+
+	fn segment_distance(p: glm::Vec2, a: glm::Vec2, b: glm::Vec2, radius: f32) -> (f32, glm::Vec2) {
+		let closest = closest_point_on_segment(p, a, b);
+		let offset = p - closest;
+		let distance = glm::length(&offset);
+		if distance > 0.0 {
+			return (distance - radius, offset / distance);
+		}
+
+		// exactly on the segment, push out along its normal
+		let ab = b - a;
+		let normal = if glm::length2(&ab) > 0.0 { glm::normalize(&glm::vec2(-ab.y, ab.x)) } else { glm::vec2(0.0, 1.0) };
+		(-radius, normal)
+	}
+
+	fn resolve(&self, object: &mut VerletObject) {
+		let (distance, normal) = self.distance(object.pos);
+		if distance < object.radius {
+			object.pos += normal * (object.radius - distance);
+			object.vel = surface_response(object.vel, normal, object.material);
+		}
+	}
+}
+
+fn rotate(v: glm::Vec2, angle: f32) -> glm::Vec2 {
+	let (sin, cos) = angle.sin_cos();
+	glm::vec2(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+// moves an object and carries the correction over into its velocity
+fn displace(object: &mut VerletObject, delta: glm::Vec2, dt: f32) {
+	object.pos += delta;
+	object.vel += delta / dt;
+}
+
+#[derive(Clone, Copy)]
+pub enum Constraint {
+	// keeps two objects `length` apart, breaks once stretched past `length * break_threshold`
+	Distance { a: usize, b: usize, length: f32, stiffness: f32, break_threshold: Option<f32> },
+	Pin { object: usize, position: glm::Vec2 },
+	// keeps the angle a-b-c (counter-clockwise from a to c around b) at `angle` radians
+	Angle { a: usize, b: usize, c: usize, angle: f32, stiffness: f32 }
+}
+
+impl Constraint {
+	// returns false once the constraint has broken
+	fn solve(&self, objects: &mut [VerletObject], dt: f32) -> bool {
+		match *self {
+			Constraint::Distance { a, b, length, stiffness, break_threshold } => {
+				let axis = objects[b].pos - objects[a].pos;
+				let distance = glm::length(&axis);
+				if let Some(threshold) = break_threshold {
+					if distance > length * threshold {
+						return false;
+					}
+				}
+				if distance == 0.0 {
+					return true;
+				}
+
+				let inv_mass_a = 1.0 / objects[a].mass;
+				let inv_mass_b = 1.0 / objects[b].mass;
+				let correction = axis * ((distance - length) / distance * stiffness / (inv_mass_a + inv_mass_b));
+				displace(&mut objects[a], correction * inv_mass_a, dt);
+				displace(&mut objects[b], -correction * inv_mass_b, dt);
+			},
+			Constraint::Angle { a, b, c, angle, stiffness } => {
+				let pivot = objects[b].pos;
+				let ba = objects[a].pos - pivot;
+				let bc = objects[c].pos - pivot;
+				let current = cross(ba, bc).atan2(glm::dot(&ba, &bc));
+				let mut error = current - angle;
+				if error > std::f32::consts::PI {
+					error -= 2.0 * std::f32::consts::PI;
+				} else if error < -std::f32::consts::PI {
+					error += 2.0 * std::f32::consts::PI;
+				}
+
+				// rotate both arms towards each other around the pivot, then move all three back
+				// so their centre of mass stays put, otherwise straightening a chain lifts it
+				let half = error * stiffness * 0.5;
+				let delta_a = rotate(ba, half) - ba;
+				let delta_c = rotate(bc, -half) - bc;
+				let (mass_a, mass_b, mass_c) = (objects[a].mass, objects[b].mass, objects[c].mass);
+				let shift = (delta_a * mass_a + delta_c * mass_c) / (mass_a + mass_b + mass_c);
+				displace(&mut objects[a], delta_a - shift, dt);
+				displace(&mut objects[b], -shift, dt);
+				displace(&mut objects[c], delta_c - shift, dt);
+			},
+			Constraint::Pin { .. } => {}
+		}
+		true
+	}
+}
+
+// ring of objects that tries to keep `pressure` times its initial area
+pub struct SoftBody {
+	pub objects: Vec<usize>,
+	pub rest_area: f32,
+	pub pressure: f32,
+	pub stiffness: f32
+}
+
+impl SoftBody {
+	fn area(&self, objects: &[VerletObject]) -> f32 {
+		let mut area = 0.0;
+		for i in 0..self.objects.len() {
+			let a = objects[self.objects[i]].pos;
+			let b = objects[self.objects[(i + 1) % self.objects.len()]].pos;
+			area += cross(a, b);
+		}
+		area * 0.5
+	}
+
+	fn solve(&self, objects: &mut [VerletObject], dt: f32) {
+		let count = self.objects.len();
+		if count < 3 {
+			return;
+		}
+
+		let error = self.area(objects) - self.rest_area * self.pressure;
+		let mut gradients: Vec<glm::Vec2> = Vec::with_capacity(count);
+		let mut weight = 0.0;
+		for i in 0..count {
+			let previous = objects[self.objects[(i + count - 1) % count]].pos;
+			let next = objects[self.objects[(i + 1) % count]].pos;
+			let gradient = glm::vec2(next.y - previous.y, previous.x - next.x) * 0.5;
+			weight += glm::length2(&gradient) / objects[self.objects[i]].mass;
+			gradients.push(gradient);
+		}
+		if weight == 0.0 {
+			return;
+		}
+
+		let lambda = -error / weight * self.stiffness;
+		for (i, gradient) in gradients.iter().enumerate() {
+			let object = &mut objects[self.objects[i]];
+			let delta = gradient * (lambda / object.mass);
+			displace(object, delta, dt);
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Handle {
+	pub index: usize,
+	pub generation: u32
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PopulationPolicy {
+	DropOldest,
+	RefuseSpawn
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edge {
+	Left,
+	Right,
+	Bottom,
+	Top
+}
+
+// stretch of the boundary objects leave through instead of bouncing off,
+// `start` and `end` run along y for the left and right edges and along x for the others
+#[derive(Clone, Copy, Debug)]
+pub struct Opening {
+	pub edge: Edge,
+	pub start: f32,
+	pub end: f32
+}
+
+impl Opening {
+	fn contains(openings: &[Opening], edge: Edge, along: f32) -> bool {
+		openings.iter().any(|opening| opening.edge == edge && along >= opening.start && along <= opening.end)
+	}
+}
+
+// what happens to the objects when the simulation changes size
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResizeMode {
+	// stretches everything along with the new size
+	Rescale,
+	// keeps positions and drops whatever ends up outside
+	Clip
+}
+
+// objects entering the region get despawned
+#[derive(Clone, Copy)]
+pub struct Drain {
+	pub min: glm::Vec2,
+	pub max: glm::Vec2
+}
+
+impl Drain {
+	fn contains(&self, p: glm::Vec2) -> bool {
+		p.x >= self.min.x && p.x <= self.max.x && p.y >= self.min.y && p.y <= self.max.y
+	}
+}
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum ColorGenerator {
+	Fixed(Color),
+	// every channel walks up or down by one per spawn and turns around at 0 and 255
+	Cycle { color: Color, change: [i32; 3] },
+	Random,
+	// steps through the colours in order
+	Palette { colors: Vec<Color>, index: usize }
+}
+
+impl ColorGenerator {
+	fn next(&mut self, rng: &mut StdRng) -> Color {
+		match self {
+			ColorGenerator::Fixed(color) => *color,
+			ColorGenerator::Cycle { color, change } => {
+				let current = *color;
+				let mut channels = [color.r as i32, color.g as i32, color.b as i32];
+				for (channel, change) in channels.iter_mut().zip(change.iter_mut()) {
+					*channel = (*channel + *change).clamp(0, 255);
+					if *channel == 255 || *channel == 0 {
+						*change = -*change;
+					}
+				}
+				*color = Color { r: channels[0] as u8, g: channels[1] as u8, b: channels[2] as u8, a: color.a };
+				current
+			},
+			ColorGenerator::Random => Color { r: rng.gen(), g: rng.gen(), b: rng.gen(), a: 255 },
+			ColorGenerator::Palette { colors, index } => {
+				if colors.is_empty() {
+					return Color { r: 255, g: 255, b: 255, a: 255 };
+				}
+				let color = colors[*index % colors.len()];
+				*index = (*index + 1) % colors.len();
+				color
+			}
+		}
+	}
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+pub enum EmitMode {
+	// objects per second
+	Continuous { rate: f32 },
+	// `count` objects at once every `interval` seconds
+	Burst { count: usize, interval: f32 }
+}
+
+#[derive(Clone)]
+pub struct Emitter {
+	pub position: glm::Vec2,
+	pub direction: glm::Vec2,
+	// half angle of the spawn cone in radians
+	pub spread: f32,
+	pub speed: (f32, f32),
+	pub mode: EmitMode,
+	pub color: ColorGenerator,
+	// most objects from this emitter alive at once
+	pub max_count: Option<usize>,
+	pub radius: f32,
+	pub mass: f32,
+	pub lifetime: Option<f32>,
+	pub material: Option<Material>,
+	pub enabled: bool,
+	timer: f32,
+	alive: Vec<Handle>,
+	rng: StdRng
+}
+
+impl Emitter {
+	pub fn new(position: glm::Vec2, direction: glm::Vec2, seed: u64) -> Emitter {
+		Emitter {
+			position,
+			direction,
+			spread: 0.0,
+			speed: (0.0, 0.0),
+			mode: EmitMode::Continuous { rate: 1.0 },
+			color: ColorGenerator::Fixed(Color { r: 255, g: 255, b: 255, a: 255 }),
+			max_count: None,
+			radius: DEFAULT_RADIUS,
+			mass: DEFAULT_MASS,
+			lifetime: None,
+			material: None,
+			enabled: true,
+			timer: 0.0,
+			alive: Vec::new(),
+			rng: StdRng::seed_from_u64(seed)
+		}
+	}
+
+	// how many objects to spawn this update
+	fn spawn_count(&mut self, dt: f32) -> usize {
+		let count = match self.mode {
+			EmitMode::Continuous { rate } => {
+				self.timer += dt * rate;
+				let count = self.timer.floor();
+				self.timer -= count;
+				count as usize
+			},
+			EmitMode::Burst { count, interval } => {
+				self.timer -= dt;
+				if self.timer > 0.0 {
+					0
+				} else {
+					self.timer += interval.max(dt);
+					count
+				}
+			}
+		};
+
+		match self.max_count {
+			Some(max_count) => count.min(max_count.saturating_sub(self.alive.len())),
+			None => count
+		}
+	}
+
+	fn emit(&mut self, physics: &mut Physics, dt: f32) {
+		self.alive.retain(|&handle| physics.is_valid(handle));
+		if !self.enabled {
+			return;
+		}
+
+		for _ in 0..self.spawn_count(dt) {
+			let angle = if self.spread > 0.0 { self.rng.gen_range(-self.spread..=self.spread) } else { 0.0 };
+			let speed = if self.speed.1 > self.speed.0 { self.rng.gen_range(self.speed.0..=self.speed.1) } else { self.speed.0 };
+			let direction = if glm::length2(&self.direction) > 0.0 { glm::normalize(&self.direction) } else { self.direction };
+			let color = self.color.next(&mut self.rng);
+
+			let handle = match physics.add_object_with(self.position.x, self.position.y, self.radius, self.mass, color) {
+				Some(handle) => handle,
+				None => return
+			};
+			let object = &mut physics[handle];
+			object.vel = rotate(direction, angle) * speed;
+			if self.lifetime.is_some() {
+				object.lifetime = self.lifetime;
+			}
+			if let Some(material) = self.material {
+				object.material = material;
+			}
+			self.alive.push(handle);
+		}
+	}
+}
+
+fn hash(x: i32, y: i32, seed: u32) -> f32 {
+	let mut h = (x as u32).wrapping_mul(0x8da6b343) ^ (y as u32).wrapping_mul(0xd8163841) ^ seed.wrapping_mul(0xcb1ab31f);
+	h ^= h >> 13;
+	h = h.wrapping_mul(0x5bd1e995);
+	h ^= h >> 15;
+	(h & 0xffffff) as f32 / 0xffffff as f32 * 2.0 - 1.0
+}
+
+// smooth value noise in [-1, 1]
+fn noise(p: glm::Vec2, seed: u32) -> f32 {
+	let x0 = p.x.floor();
+	let y0 = p.y.floor();
+	let tx = p.x - x0;
+	let ty = p.y - y0;
+	let sx = tx * tx * (3.0 - 2.0 * tx);
+	let sy = ty * ty * (3.0 - 2.0 * ty);
+	let (x0, y0) = (x0 as i32, y0 as i32);
+
+	let top = hash(x0, y0, seed) + (hash(x0 + 1, y0, seed) - hash(x0, y0, seed)) * sx;
+	let bottom = hash(x0, y0 + 1, seed) + (hash(x0 + 1, y0 + 1, seed) - hash(x0, y0 + 1, seed)) * sx;
+	top + (bottom - top) * sy
+}
+
+#[derive(Clone, Copy)]
+pub enum Falloff {
+	None,
+	// fades to zero at the field radius
+	Linear,
+	InverseSquare
+}
+
+#[derive(Clone, Copy)]
+pub enum ForceField {
+	// pulls objects towards `position`, a negative strength pushes them away
+	Point { position: glm::Vec2, strength: f32, radius: f32, falloff: Falloff },
+	// spins objects counter-clockwise around `position`, clockwise for a negative strength
+	Vortex { position: glm::Vec2, strength: f32, radius: f32 },
+	// `gust_strength` is how much the wind varies, `gust_frequency` how often per second
+	Wind { acceleration: glm::Vec2, gust_strength: f32, gust_frequency: f32, seed: u32 },
+	// swirling noise, `scale` is the size of a swirl in world units
+	Turbulence { strength: f32, scale: f32, speed: f32, seed: u32 }
+}
+
+impl ForceField {
+	pub fn acceleration(&self, pos: glm::Vec2, time: f32) -> glm::Vec2 {
+		match *self {
+			ForceField::Point { position, strength, radius, falloff } => {
+				let offset = position - pos;
+				let distance = glm::length(&offset);
+				if distance == 0.0 || distance > radius {
+					return glm::vec2(0.0, 0.0);
+				}
+				let scale = match falloff {
+					Falloff::None => 1.0,
+					Falloff::Linear => 1.0 - distance / radius,
+					Falloff::InverseSquare => 1.0 / (1.0 + distance * distance)
+				};
+				offset / distance * (strength * scale)
+			},
+			ForceField::Vortex { position, strength, radius } => {
+				let offset = pos - position;
+				let distance = glm::length(&offset);
+				if distance == 0.0 || distance > radius {
+					return glm::vec2(0.0, 0.0);
+				}
+				glm::vec2(-offset.y, offset.x) / distance * (strength * (1.0 - distance / radius))
+			},
+			ForceField::Wind { acceleration, gust_strength, gust_frequency, seed } => {
+				let gust = noise(glm::vec2(time * gust_frequency, 0.0), seed);
+				acceleration * (1.0 + gust_strength * gust)
+			},
+			ForceField::Turbulence { strength, scale, speed, seed } => {
+				// curl of the noise so the flow swirls instead of bunching objects up
+				let p = pos / scale.max(f32::EPSILON) + glm::vec2(time * speed, 0.0);
+				let epsilon = 0.01;
+				let dx = noise(p + glm::vec2(epsilon, 0.0), seed) - noise(p - glm::vec2(epsilon, 0.0), seed);
+				let dy = noise(p + glm::vec2(0.0, epsilon), seed) - noise(p - glm::vec2(0.0, epsilon), seed);
+				glm::vec2(dy, -dx) / (2.0 * epsilon) * strength
+			}
+		}
+	}
+}
+
+// stripe count used in deterministic mode, independent of the thread count
+const DETERMINISTIC_STRIPES: usize = 16;
+// below this many objects handing collisions to the worker pool costs more than it saves
+const PARALLEL_MIN_OBJECTS: usize = 2000;
+
+#[derive(Clone, Copy)]
+struct ObjectsPtr(*mut VerletObject);
+
+// threads only ever touch objects in disjoint sets of grid columns
+unsafe impl Send for ObjectsPtr {}
+unsafe impl Sync for ObjectsPtr {}
+
+// checks every cell in `columns` against its 3x3 neighbourhood, the caller
+// guarantees nothing else touches objects in `columns` or the columns next to it
+unsafe fn collide_columns(grid: &Grid, objects: ObjectsPtr, columns: std::ops::Range<usize>) {
+	let mut cell: Vec<u32> = Vec::new();
+	let mut neighbours: Vec<u32> = Vec::new();
+	let (w, h) = (grid.width(), grid.height());
+	for y in 0..h {
+		for x in columns.clone() {
+			cell.clear();
+			grid.collect(x, y, &mut cell);
+			if cell.is_empty() {
+				continue;
+			}
+
+			neighbours.clear();
+			for n_y in y.saturating_sub(1)..(y + 2).min(h) {
+				for n_x in x.saturating_sub(1)..(x + 2).min(w) {
+					grid.collect(n_x, n_y, &mut neighbours);
+				}
+			}
+
+			for &v_i1 in cell.iter() {
+				for &v_i2 in neighbours.iter() {
+					// every pair shows up from both sides, only handle it once
+					if v_i1 < v_i2 {
+						let first = &mut *objects.0.add(v_i1 as usize);
+						let second = &mut *objects.0.add(v_i2 as usize);
+						second.check_collision(first);
+					}
+				}
+			}
+		}
+	}
+}
+
+// updates several simulations at once on the worker pool, in at most `threads` chunks
+pub fn update_all(simulations: &mut [Physics], dt: f32, threads: usize) {
+	if threads <= 1 || simulations.len() <= 1 {
+		for simulation in simulations.iter_mut() {
+			simulation.update(dt);
+		}
+		return;
+	}
+
+	let chunk_size = simulations.len().div_ceil(threads);
+	rayon::scope(|scope| {
+		for chunk in simulations.chunks_mut(chunk_size) {
+			scope.spawn(move |_| {
+				for simulation in chunk.iter_mut() {
+					simulation.update(dt);
+				}
+			});
+		}
+	});
+}
+
+pub const DEFAULT_RADIUS: f32 = 0.5;
+pub const DEFAULT_MASS: f32 = 1.0;
+
+// the shape of a cloth and how it holds together
+#[derive(Clone, Copy)]
+pub struct ClothConfig {
+	pub columns: usize,
+	pub rows: usize,
+	// rest distance between neighbours
+	pub spacing: f32,
+	pub stiffness: f32,
+	// links stretched past this many times their length tear
+	pub break_threshold: Option<f32>
+}
+
+impl Default for ClothConfig {
+	fn default() -> Self {
+		ClothConfig {
+			columns: 20,
+			rows: 15,
+			spacing: 1.5,
+			stiffness: 1.0,
+			break_threshold: None
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+pub struct PhysicsConfig {
+	// substeps per update, raised up to max_step_count when objects move too fast
+	pub step_count: usize,
+	pub max_step_count: usize,
+	// largest distance an object may travel in one substep, as a fraction of its radius
+	pub max_step_distance: Option<f32>,
+	pub gravity: glm::Vec2,
+	pub collision_iterations: usize,
+	pub constraint_iterations: usize,
+	// fraction of the velocity lost per second
+	pub velocity_damping: f32,
+	// quadratic drag, deceleration is `air_drag * speed^2`
+	pub air_drag: f32,
+	pub default_material: Material,
+	pub max_velocity: Option<f32>,
+	// threads used to solve collisions
+	pub threads: usize,
+	// solve in the same order whatever the thread count, so results match bit for bit
+	pub deterministic: bool,
+	pub max_objects: Option<usize>,
+	pub population_policy: PopulationPolicy,
+	// lifetime given to objects from add_object
+	pub default_lifetime: Option<f32>,
+	// objects fade out over the last `fade_time` seconds of their lifetime
+	pub fade_time: f32
+}
+
+impl Default for PhysicsConfig {
+	fn default() -> Self {
+		PhysicsConfig {
+			step_count: 8,
+			max_step_count: 64,
+			max_step_distance: Some(0.5),
+			gravity: glm::vec2(0.0, -98.0),
+			collision_iterations: 1,
+			constraint_iterations: 2,
+			velocity_damping: 0.0,
+			air_drag: 0.0,
+			default_material: Material::DEFAULT,
+			max_velocity: None,
+			threads: 1,
+			deterministic: false,
+			max_objects: None,
+			population_policy: PopulationPolicy::DropOldest,
+			default_lifetime: None,
+			fade_time: 1.0
+		}
+	}
 }
 
 pub struct Physics {
 	pub size: glm::Vec2,
+	pub config: PhysicsConfig,
 	objects: Vec<VerletObject>,
-	grid: Array2D<Cell>
+	generations: Vec<u32>,
+	free: Vec<usize>,
+	// handles in spawn order, may contain stale entries
+	spawn_order: std::collections::VecDeque<Handle>,
+	object_count: usize,
+	drains: Vec<Drain>,
+	grid: Grid,
+	colliders: Vec<Collider>,
+	// collider indices overlapping each grid cell
+	collider_cells: Array2D<Vec<u32>>,
+	constraints: Vec<Constraint>,
+	soft_bodies: Vec<SoftBody>,
+	emitters: Vec<Emitter>,
+	force_fields: Vec<ForceField>,
+	openings: Vec<Opening>,
+	time: f32
 }
 
-const STEP_COUNT: usize = 8;
-const GRAVITY: f32 = -98.0;
-
 impl Physics {
 	pub fn new(w: usize, h: usize) -> Physics {
+		Physics::with_config(w, h, PhysicsConfig::default())
+	}
+
+	pub fn with_config(w: usize, h: usize, config: PhysicsConfig) -> Physics {
+		let grid = Grid::new(glm::vec2(w as f32, h as f32), DEFAULT_RADIUS * 2.0);
 		Physics {
 			size: glm::vec2(w as f32, h as f32),
+			config,
 			objects: Vec::with_capacity(w * h),
-			grid: Array2D::new(w, h, Cell { count: 0, indices: [0; 7] })
+			generations: Vec::with_capacity(w * h),
+			free: Vec::new(),
+			spawn_order: std::collections::VecDeque::new(),
+			object_count: 0,
+			drains: Vec::new(),
+			collider_cells: Array2D::new(grid.width(), grid.height(), Vec::new()),
+			grid,
+			colliders: Vec::new(),
+			constraints: Vec::new(),
+			soft_bodies: Vec::new(),
+			emitters: Vec::new(),
+			force_fields: Vec::new(),
+			openings: Vec::new(),
+			time: 0.0
+		}
+	}
+
+	pub fn add_object(&mut self, x: f32, y: f32, color: Color) -> Option<Handle> {
+		self.add_object_with(x, y, DEFAULT_RADIUS, DEFAULT_MASS, color)
+	}
+
+	pub fn add_object_with(&mut self, x: f32, y: f32, radius: f32, mass: f32, color: Color) -> Option<Handle> {
+		if let Some(max_objects) = self.config.max_objects {
+			while self.object_count >= max_objects {
+				if self.config.population_policy == PopulationPolicy::RefuseSpawn {
+					return None;
+				}
+				let oldest = self.spawn_order.pop_front()?;
+				self.remove_object(oldest);
+			}
 		}
+
+		let handle = self.spawn(glm::vec2(x, y), radius, mass, color);
+		self.objects[handle.index].lifetime = self.config.default_lifetime;
+		self.track(handle);
+		Some(handle)
 	}
 
-	pub fn add_object(&mut self, x: f32, y: f32, color: Color) -> usize {
-		let index = self.objects.len();
-		self.objects.push(VerletObject {
-			pos: glm::vec2(x, y),
+	// adds an object regardless of the population limit, only tracked objects can be dropped
+	// for new ones so ropes, cloth and soft bodies never lose members that way
+	fn spawn(&mut self, pos: glm::Vec2, radius: f32, mass: f32, color: Color) -> Handle {
+		// grow the grid cells so neighbouring cells still cover every possible contact
+		if radius * 2.0 > self.grid.cell_size {
+			self.grid = Grid::new(self.size, radius * 2.0);
+			self.rebuild_collider_cells();
+		}
+
+		let object = VerletObject {
+			pos,
+			last_pos: pos,
 			vel: glm::vec2(0.0, 0.0),
 			acc: glm::vec2(0.0, 0.0),
-			color: color
+			radius,
+			mass,
+			color,
+			material: self.config.default_material,
+			lifetime: None,
+			alive: true
+		};
+		let index = match self.free.pop() {
+			Some(index) => {
+				self.objects[index] = object;
+				index
+			},
+			None => {
+				self.objects.push(object);
+				self.generations.push(0);
+				self.objects.len() - 1
+			}
+		};
+
+		self.object_count += 1;
+		Handle { index, generation: self.generations[index] }
+	}
+
+	// remembers the object as the newest one the population limit can drop
+	fn track(&mut self, handle: Handle) {
+		self.spawn_order.push_back(handle);
+		if self.spawn_order.len() > 2 * self.object_count + 64 {
+			let objects = &self.objects;
+			let generations = &self.generations;
+			self.spawn_order.retain(|handle| objects[handle.index].alive && generations[handle.index] == handle.generation);
+		}
+	}
+
+	pub fn remove_object(&mut self, handle: Handle) -> bool {
+		if !self.is_valid(handle) {
+			return false;
+		}
+		let index = handle.index;
+		self.objects[index].alive = false;
+		self.generations[index] = self.generations[index].wrapping_add(1);
+		self.free.push(index);
+		self.object_count -= 1;
+
+		// anything attached to the object goes with it
+		self.constraints.retain(|constraint| match *constraint {
+			Constraint::Distance { a, b, .. } => a != index && b != index,
+			Constraint::Pin { object, .. } => object != index,
+			Constraint::Angle { a, b, c, .. } => a != index && b != index && c != index
 		});
+		for soft_body in self.soft_bodies.iter_mut() {
+			soft_body.objects.retain(|&object| object != index);
+		}
+		true
+	}
+
+	pub fn is_valid(&self, handle: Handle) -> bool {
+		handle.index < self.objects.len() && self.objects[handle.index].alive && self.generations[handle.index] == handle.generation
+	}
+
+	pub fn get(&self, handle: Handle) -> Option<&VerletObject> {
+		if self.is_valid(handle) { Some(&self.objects[handle.index]) } else { None }
+	}
+
+	#[allow(dead_code)]
+	pub fn get_mut(&mut self, handle: Handle) -> Option<&mut VerletObject> {
+		if self.is_valid(handle) { Some(&mut self.objects[handle.index]) } else { None }
+	}
+
+	pub fn add_force_field(&mut self, force_field: ForceField) -> usize {
+		let index = self.force_fields.len();
+		self.force_fields.push(force_field);
+		index
+	}
+
+	#[allow(dead_code)]
+	pub fn force_field_mut(&mut self, index: usize) -> &mut ForceField {
+		&mut self.force_fields[index]
+	}
+
+	pub fn add_emitter(&mut self, emitter: Emitter) -> usize {
+		let index = self.emitters.len();
+		self.emitters.push(emitter);
+		index
+	}
+
+	#[allow(dead_code)]
+	pub fn emitter_mut(&mut self, index: usize) -> &mut Emitter {
+		&mut self.emitters[index]
+	}
+
+	pub fn add_drain(&mut self, min: glm::Vec2, max: glm::Vec2) -> usize {
+		let index = self.drains.len();
+		self.drains.push(Drain { min, max });
+		index
+	}
+
+	pub fn add_collider(&mut self, collider: Collider) -> usize {
+		let index = self.colliders.len();
+		self.colliders.push(collider);
+		self.rebuild_collider_cells();
+		index
+	}
+
+	pub fn clear_colliders(&mut self) {
+		self.colliders.clear();
+		self.rebuild_collider_cells();
+	}
+
+	#[allow(dead_code)]
+	pub fn colliders(&self) -> &Vec<Collider> {
+		&self.colliders
+	}
+
+	pub fn add_constraint(&mut self, constraint: Constraint) -> usize {
+		let index = self.constraints.len();
+		self.constraints.push(constraint);
 		index
 	}
 
+	#[allow(dead_code)]
+	pub fn constraints(&self) -> &Vec<Constraint> {
+		&self.constraints
+	}
+
+	fn link(&mut self, a: Handle, b: Handle, stiffness: f32, break_threshold: Option<f32>) {
+		let length = glm::distance(&self.objects[a.index].pos, &self.objects[b.index].pos);
+		self.add_constraint(Constraint::Distance { a: a.index, b: b.index, length, stiffness, break_threshold });
+	}
+
+	fn pin(&mut self, object: Handle) {
+		let position = self.objects[object.index].pos;
+		self.add_constraint(Constraint::Pin { object: object.index, position });
+	}
+
+	// chain of objects from `start` to `end`, optionally pinned at `start`
+	pub fn add_rope(&mut self, start: glm::Vec2, end: glm::Vec2, segments: usize, stiffness: f32, pinned: bool, color: Color) -> Vec<Handle> {
+		let segments = segments.max(1);
+		let mut indices = Vec::with_capacity(segments + 1);
+		for i in 0..=segments {
+			let pos = start + (end - start) * (i as f32 / segments as f32);
+			indices.push(self.spawn(pos, DEFAULT_RADIUS, DEFAULT_MASS, color));
+		}
+		for pair in indices.windows(2) {
+			self.link(pair[0], pair[1], stiffness, None);
+		}
+		if pinned {
+			self.pin(indices[0]);
+		}
+		indices
+	}
+
+	// keeps the angles along a chain of objects, like a rope, the way they are now
+	pub fn add_bending(&mut self, chain: &[Handle], stiffness: f32) {
+		for triple in chain.windows(3) {
+			let (a, b, c) = (triple[0].index, triple[1].index, triple[2].index);
+			let ba = self.objects[a].pos - self.objects[b].pos;
+			let bc = self.objects[c].pos - self.objects[b].pos;
+			let angle = cross(ba, bc).atan2(glm::dot(&ba, &bc));
+			self.add_constraint(Constraint::Angle { a, b, c, angle, stiffness });
+		}
+	}
+
+	// grid of objects hanging down from `top_left`, pinned along its top row, stored row by row
+	pub fn add_cloth(&mut self, top_left: glm::Vec2, cloth: &ClothConfig, color: Color) -> Vec<Handle> {
+		let ClothConfig { columns, rows, stiffness, break_threshold, .. } = *cloth;
+		let spacing = cloth.spacing.max(DEFAULT_RADIUS * 2.0);
+		let mut indices = Vec::with_capacity(columns * rows);
+		for y in 0..rows {
+			for x in 0..columns {
+				let pos = top_left + glm::vec2(x as f32 * spacing, -(y as f32) * spacing);
+				indices.push(self.spawn(pos, DEFAULT_RADIUS, DEFAULT_MASS, color));
+			}
+		}
+		for y in 0..rows {
+			for x in 0..columns {
+				let index = indices[x + y * columns];
+				if x + 1 < columns {
+					self.link(index, indices[x + 1 + y * columns], stiffness, break_threshold);
+				}
+				if y + 1 < rows {
+					self.link(index, indices[x + (y + 1) * columns], stiffness, break_threshold);
+				}
+			}
+		}
+		for &index in indices.iter().take(columns) {
+			self.pin(index);
+		}
+		indices
+	}
+
+	// pressurised ring of `count` objects, returns the soft body index
+	pub fn add_soft_body(&mut self, center: glm::Vec2, radius: f32, count: usize, pressure: f32, stiffness: f32, color: Color) -> usize {
+		let count = count.max(3);
+		let mut indices = Vec::with_capacity(count);
+		for i in 0..count {
+			let angle = i as f32 / count as f32 * 2.0 * std::f32::consts::PI;
+			let pos = center + glm::vec2(angle.cos(), angle.sin()) * radius;
+			indices.push(self.spawn(pos, DEFAULT_RADIUS, DEFAULT_MASS, color));
+		}
+		for i in 0..count {
+			self.link(indices[i], indices[(i + 1) % count], stiffness, None);
+		}
+
+		let objects = indices.iter().map(|handle| handle.index).collect();
+		let mut soft_body = SoftBody { objects, rest_area: 0.0, pressure, stiffness };
+		soft_body.rest_area = soft_body.area(&self.objects);
+		let index = self.soft_bodies.len();
+		self.soft_bodies.push(soft_body);
+		index
+	}
+
+	fn rebuild_collider_cells(&mut self) {
+		self.collider_cells = Array2D::new(self.grid.width(), self.grid.height(), Vec::new());
+
+		// objects are at most one cell wide, so grow the bounds by a cell
+		let margin = glm::vec2(self.grid.cell_size, self.grid.cell_size);
+		for (index, collider) in self.colliders.iter().enumerate() {
+			let (min, max) = collider.bounds();
+			let (x_start, y_start) = self.grid.cell_of(min - margin);
+			let (x_end, y_end) = self.grid.cell_of(max + margin);
+			for y in y_start..=y_end {
+				for x in x_start..=x_end {
+					self.collider_cells[(x, y)].push(index as u32);
+				}
+			}
+		}
+	}
+
+	pub fn set_openings(&mut self, openings: Vec<Opening>) {
+		self.openings = openings;
+	}
+
+	// removes and returns the objects that went out through an opening
+	pub fn take_departed(&mut self) -> Vec<VerletObject> {
+		let mut departed: Vec<VerletObject> = Vec::new();
+		if self.openings.is_empty() {
+			return departed;
+		}
+		for index in 0..self.objects.len() {
+			let object = self.objects[index];
+			if object.alive && (object.pos.x < 0.0 || object.pos.x > self.size.x || object.pos.y < 0.0 || object.pos.y > self.size.y) {
+				self.remove_object(Handle { index, generation: self.generations[index] });
+				departed.push(object);
+			}
+		}
+		departed
+	}
+
+	// adds an object that came from another simulation, keeping its motion, age and material,
+	// it already counted against a population limit so it can't be refused here
+	pub fn insert_object(&mut self, object: VerletObject) -> Handle {
+		let handle = self.spawn(object.pos, object.radius, object.mass, object.color);
+		self.objects[handle.index] = VerletObject { alive: true, ..object };
+		self.track(handle);
+		handle
+	}
+
+	pub fn resize(&mut self, w: usize, h: usize, mode: ResizeMode) {
+		let size = glm::vec2(w.max(1) as f32, h.max(1) as f32);
+		match mode {
+			ResizeMode::Rescale => {
+				let scale = size.component_div(&self.size);
+				for object in self.objects.iter_mut().filter(|object| object.alive) {
+					object.pos = object.pos.component_mul(&scale);
+					object.last_pos = object.last_pos.component_mul(&scale);
+				}
+				for emitter in self.emitters.iter_mut() {
+					emitter.position = emitter.position.component_mul(&scale);
+				}
+				for drain in self.drains.iter_mut() {
+					drain.min = drain.min.component_mul(&scale);
+					drain.max = drain.max.component_mul(&scale);
+				}
+				for collider in self.colliders.iter_mut() {
+					collider.scale(scale);
+				}
+				for constraint in self.constraints.iter_mut() {
+					if let Constraint::Pin { position, .. } = constraint {
+						*position = position.component_mul(&scale);
+					}
+				}
+				for force_field in self.force_fields.iter_mut() {
+					match force_field {
+						ForceField::Point { position, .. } | ForceField::Vortex { position, .. } => *position = position.component_mul(&scale),
+						_ => {}
+					}
+				}
+			},
+			ResizeMode::Clip => {
+				for index in 0..self.objects.len() {
+					let pos = self.objects[index].pos;
+					if self.objects[index].alive && (pos.x >= size.x || pos.y >= size.y) {
+						self.remove_object(Handle { index, generation: self.generations[index] });
+					}
+				}
+				for emitter in self.emitters.iter_mut() {
+					emitter.position = glm::min2(&emitter.position, &(size - glm::vec2(1.0, 1.0)));
+				}
+			}
+		}
+
+		self.size = size;
+		self.grid = Grid::new(size, self.grid.cell_size);
+		self.rebuild_collider_cells();
+	}
+
 	pub fn update(&mut self, dt: f32) {
-		let step_dt = dt / (STEP_COUNT as f32);
-		for _ in [0..STEP_COUNT] {
-			self.apply_collisions();
+		for object in self.objects.iter_mut() {
+			object.last_pos = object.pos;
+		}
+		self.despawn(dt);
+
+		let mut emitters = std::mem::take(&mut self.emitters);
+		for emitter in emitters.iter_mut() {
+			emitter.emit(self, dt);
+		}
+		self.emitters = emitters;
+
+		let step_count = self.step_count(dt);
+		let step_dt = dt / (step_count as f32);
+		for _ in 0..step_count {
+			for _ in 0..self.config.collision_iterations {
+				self.apply_collisions();
+			}
 			self.movement(step_dt);
+			for _ in 0..self.config.constraint_iterations {
+				self.solve_constraints(step_dt);
+			}
+			self.apply_colliders();
+		}
+	}
+
+	pub fn solve_constraints(&mut self, dt: f32) {
+		if self.constraints.is_empty() && self.soft_bodies.is_empty() {
+			return;
+		}
+
+		let objects = &mut self.objects;
+		self.constraints.retain(|constraint| constraint.solve(objects, dt));
+		for soft_body in self.soft_bodies.iter() {
+			soft_body.solve(objects, dt);
+		}
+
+		// pins go last so nothing can drag a pinned object away
+		for constraint in self.constraints.iter() {
+			if let Constraint::Pin { object, position } = *constraint {
+				objects[object].pos = position;
+				objects[object].vel = glm::vec2(0.0, 0.0);
+			}
 		}
 	}
 
+	// removes objects whose lifetime ran out or that entered a drain
+	fn despawn(&mut self, dt: f32) {
+		let mut expired: Vec<Handle> = Vec::new();
+		for (index, object) in self.objects.iter_mut().enumerate().filter(|(_, object)| object.alive) {
+			if let Some(lifetime) = object.lifetime.as_mut() {
+				*lifetime -= dt;
+			}
+			let dead = object.lifetime.is_some_and(|lifetime| lifetime <= 0.0);
+			if dead || self.drains.iter().any(|drain| drain.contains(object.pos)) {
+				expired.push(Handle { index, generation: self.generations[index] });
+			}
+		}
+		for handle in expired {
+			self.remove_object(handle);
+		}
+	}
+
+	pub fn step_count(&self, dt: f32) -> usize {
+		let step_count = self.config.step_count.max(1);
+		let fraction = match self.config.max_step_distance {
+			Some(fraction) if fraction > 0.0 => fraction,
+			_ => return step_count
+		};
+
+		// how many radii the fastest object covers per second
+		let max_velocity = self.config.max_velocity.unwrap_or(f32::INFINITY);
+		let max_rate = self.objects.iter().filter(|object| object.alive).fold(0.0, |max: f32, object| {
+			let speed = glm::length(&object.vel).min(max_velocity);
+			max.max(speed / object.radius)
+		});
+
+		let needed = (max_rate * dt / fraction).ceil() as usize;
+		needed.clamp(step_count, self.config.max_step_count.max(step_count))
+	}
+
 	pub fn apply_collisions(&mut self) {
-		for (index, object) in self.objects.iter().enumerate() {
-			let cell: &mut Cell = &mut self.grid[(object.pos.x as usize, object.pos.y as usize)];
-			cell.indices[cell.count as usize] = index as u8;
+		// rebuild the grid
+		self.grid.clear();
+		for (index, object) in self.objects.iter().enumerate().filter(|(_, object)| object.alive) {
+			let (x, y) = self.grid.cell_of(object.pos);
+			self.grid.insert(x, y, index as u32);
+		}
+
+		let w = self.grid.width();
+		let threads = if self.object_count < PARALLEL_MIN_OBJECTS { 1 } else { self.config.threads.max(1) };
+		if threads == 1 && !self.config.deterministic {
+			unsafe { collide_columns(&self.grid, ObjectsPtr(self.objects.as_mut_ptr()), 0..w) };
+			return;
+		}
+
+		// stripes need to be at least two cells wide so the stripes of one pass never touch the same objects
+		let stripe_count = if self.config.deterministic { DETERMINISTIC_STRIPES } else { threads * 2 };
+		let stripe_count = stripe_count.min(w / 2).max(1);
+		let stripes: Vec<std::ops::Range<usize>> = (0..stripe_count).map(|i| (i * w / stripe_count)..((i + 1) * w / stripe_count)).collect();
+
+		// even stripes first, then odd stripes
+		let objects = ObjectsPtr(self.objects.as_mut_ptr());
+		let grid = &self.grid;
+		for pass in 0..2 {
+			let pass_stripes: Vec<std::ops::Range<usize>> = stripes.iter().skip(pass).step_by(2).cloned().collect();
+			if threads == 1 || pass_stripes.len() == 1 {
+				for stripe in pass_stripes {
+					unsafe { collide_columns(grid, objects, stripe) };
+				}
+				continue;
+			}
+
+			let chunk_size = pass_stripes.len().div_ceil(threads);
+			rayon::scope(|scope| {
+				for chunk in pass_stripes.chunks(chunk_size) {
+					scope.spawn(move |_| {
+						for stripe in chunk.iter() {
+							unsafe { collide_columns(grid, objects, stripe.clone()) };
+						}
+					});
+				}
+			});
 		}
+	}
 
+	pub fn apply_collisions_brute_force(&mut self) {
 		for v_i1 in 0..self.objects.len() {
 			for v_i2 in (v_i1 + 1)..self.objects.len() {
-				let (first_slice, second_slice) = self.objects.split_at_mut(v_i2);
-				second_slice[0].check_collision(&mut first_slice[v_i1]);
+				if self.objects[v_i1].alive && self.objects[v_i2].alive {
+					self.collide_pair(v_i1, v_i2);
+				}
+			}
+		}
+	}
+
+	fn collide_pair(&mut self, v_i1: usize, v_i2: usize) {
+		let (first_slice, second_slice) = self.objects.split_at_mut(v_i2);
+		second_slice[0].check_collision(&mut first_slice[v_i1]);
+	}
+
+	pub fn apply_colliders(&mut self) {
+		if self.colliders.is_empty() {
+			return;
+		}
+		for object in self.objects.iter_mut().filter(|object| object.alive) {
+			let (x, y) = self.grid.cell_of(object.pos);
+			for &index in self.collider_cells[(x, y)].iter() {
+				self.colliders[index as usize].resolve(object);
 			}
 		}
 	}
 
 	pub fn movement(&mut self, dt: f32) {
-		for object in self.objects.iter_mut() {
+		let damping = (1.0 - self.config.velocity_damping.clamp(0.0, 1.0)).powf(dt);
+		let time = self.time;
+		self.time += dt;
+		let openings = &self.openings;
+		for object in self.objects.iter_mut().filter(|object| object.alive) {
 			
 			// movement
-			object.movement(dt, glm::vec2(0.0, GRAVITY));
+			let mut acc = self.config.gravity;
+			for force_field in self.force_fields.iter() {
+				acc += force_field.acceleration(object.pos, time);
+			}
+			acc -= object.vel * (self.config.air_drag * glm::length(&object.vel));
+			object.movement(dt, acc);
 
-			// constraint
-			let margin: f32 = 0.0;
-			if object.pos.x < margin {
+			// damping
+			object.vel *= damping;
+			if let Some(max_velocity) = self.config.max_velocity {
+				let speed = glm::length(&object.vel);
+				if speed > max_velocity {
+					object.vel *= max_velocity / speed;
+				}
+			}
+
+			// constraint, except where the edge is open
+			let margin: f32 = object.radius;
+			if object.pos.x < margin && !Opening::contains(openings, Edge::Left, object.pos.y) {
 				object.pos.x = margin;
-				object.vel.x = 0.0;
-			} else if object.pos.x > self.size.x - margin {
+				object.vel = surface_response(object.vel, glm::vec2(1.0, 0.0), object.material);
+			} else if object.pos.x > self.size.x - margin && !Opening::contains(openings, Edge::Right, object.pos.y) {
 				object.pos.x = self.size.x - margin;
-				object.vel.x = 0.0;
+				object.vel = surface_response(object.vel, glm::vec2(-1.0, 0.0), object.material);
 			}
-			if object.pos.y < margin {
+			if object.pos.y < margin && !Opening::contains(openings, Edge::Bottom, object.pos.x) {
 				object.pos.y = margin;
-				object.vel.y = 0.0;
-			} else if object.pos.y > self.size.y - margin {
+				object.vel = surface_response(object.vel, glm::vec2(0.0, 1.0), object.material);
+			} else if object.pos.y > self.size.y - margin && !Opening::contains(openings, Edge::Top, object.pos.x) {
 				object.pos.y = self.size.y - margin;
-				object.vel.y = 0.0;
+				object.vel = surface_response(object.vel, glm::vec2(0.0, -1.0), object.material);
 			}
 		}
 	}
 
+	pub fn kinetic_energy(&self) -> f32 {
+		self.objects.iter().filter(|object| object.alive).map(|object| 0.5 * object.mass * glm::length2(&object.vel)).sum()
+	}
+
+	pub fn object_count(&self) -> usize {
+		self.object_count
+	}
+
+	fn faded_color(&self, object: &VerletObject) -> Color {
+		match object.lifetime {
+			Some(lifetime) if self.config.fade_time > 0.0 && lifetime < self.config.fade_time => {
+				let fade = (lifetime / self.config.fade_time).max(0.0);
+				Color {
+					r: (object.color.r as f32 * fade) as u8,
+					g: (object.color.g as f32 * fade) as u8,
+					b: (object.color.b as f32 * fade) as u8,
+					a: (object.color.a as f32 * fade) as u8
+				}
+			},
+			_ => object.color
+		}
+	}
+
+	#[allow(dead_code)]
 	pub fn render(&self, buffer: &mut Array2D<Color>) {
-		for object in self.objects.iter() {
-			buffer[(object.pos.x as usize, object.pos.y as usize)] = object.color;
+		self.render_interpolated(buffer, 1.0);
+	}
+
+	// draws the objects `alpha` of the way from their position before the last update to the current one
+	pub fn render_interpolated(&self, buffer: &mut Array2D<Color>, alpha: f32) {
+		let (w, h) = (buffer.width() as i32, buffer.height() as i32);
+		for object in self.objects.iter().filter(|object| object.alive) {
+			let color = self.faded_color(object);
+			let pos = object.last_pos + (object.pos - object.last_pos) * alpha;
+			if object.radius <= DEFAULT_RADIUS {
+				let x = pos.x as i32;
+				let y = pos.y as i32;
+				if x >= 0 && x < w && y >= 0 && y < h {
+					buffer[(x as usize, y as usize)] = color;
+				}
+				continue;
+			}
+
+			// fill every texel whose centre lies inside the object
+			let radius2 = object.radius * object.radius;
+			let x_start = ((pos.x - object.radius).floor() as i32).max(0);
+			let x_end = ((pos.x + object.radius).ceil() as i32).min(w);
+			let y_start = ((pos.y - object.radius).floor() as i32).max(0);
+			let y_end = ((pos.y + object.radius).ceil() as i32).min(h);
+			for y in y_start..y_end {
+				for x in x_start..x_end {
+					let offset = glm::vec2(x as f32 + 0.5, y as f32 + 0.5) - pos;
+					if glm::length2(&offset) <= radius2 {
+						buffer[(x as usize, y as usize)] = color;
+					}
+				}
+			}
 		}
 	}
 
+	// the objects as circles in simulation units, `alpha` of the way through the last update
+	pub fn circles(&self, circles: &mut Vec<Circle>, alpha: f32) {
+		circles.clear();
+		circles.extend(self.objects.iter().filter(|object| object.alive).map(|object| {
+			let pos = object.last_pos + (object.pos - object.last_pos) * alpha;
+			Circle { x: pos.x, y: pos.y, radius: object.radius, color: self.faded_color(object) }
+		}));
+	}
+
 }
 
+// slots of removed objects get reused, prefer handles to keep track of one object
 impl std::ops::Index<usize> for Physics {
 	type Output = VerletObject;
 	fn index(&self, index: usize) -> &Self::Output {
+		assert!(self.objects[index].alive, "physics object {} was removed", index);
 		&self.objects[index]
 	}
 }
 
 impl std::ops::IndexMut<usize> for Physics {
 	fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+		assert!(self.objects[index].alive, "physics object {} was removed", index);
 		&mut self.objects[index]
 	}
 }
+
+impl std::ops::Index<Handle> for Physics {
+	type Output = VerletObject;
+	fn index(&self, handle: Handle) -> &Self::Output {
+		self.get(handle).expect("stale physics handle")
+	}
+}
+
+impl std::ops::IndexMut<Handle> for Physics {
+	fn index_mut(&mut self, handle: Handle) -> &mut Self::Output {
+		assert!(self.is_valid(handle), "stale physics handle");
+		&mut self.objects[handle.index]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+
+	fn scattered(count: usize, w: usize, h: usize) -> Physics {
+		let mut rng = StdRng::seed_from_u64(1);
+		let mut physics = Physics::new(w, h);
+		for _ in 0..count {
+			physics.add_object(rng.gen_range(0.0..w as f32), rng.gen_range(0.0..h as f32), WHITE);
+		}
+		physics
+	}
+
+	fn run(physics: &mut Physics, seconds: f32) {
+		for _ in 0..(seconds * 30.0) as usize {
+			physics.update(1.0 / 30.0);
+		}
+	}
+
+	// one object launched from `start` at `velocity` into a world prepared by `setup`, after `seconds`
+	fn launch(setup: impl FnOnce(&mut Physics), start: glm::Vec2, velocity: glm::Vec2, seconds: f32) -> (Physics, Handle) {
+		let mut physics = Physics::new(120, 68);
+		setup(&mut physics);
+		let handle = physics.add_object(start.x, start.y, WHITE).unwrap();
+		physics[handle].vel = velocity;
+		run(&mut physics, seconds);
+		(physics, handle)
+	}
+
+	// drops an object moving sideways onto the floor, returns the highest point after the first
+	// bounce, the fraction of its energy left and its sideways speed at the end
+	fn drop(material: Material, seconds: f32) -> (f32, f32, f32) {
+		let height = 60.0;
+		let (mut physics, handle) = launch(|physics| physics.config.default_material = material, glm::vec2(60.0, height), glm::vec2(10.0, 0.0), 0.0);
+
+		let gravity = -physics.config.gravity.y;
+		let start_energy = physics.kinetic_energy() + gravity * height;
+		let mut peak: f32 = 0.0;
+		let mut bounced = false;
+		for _ in 0..(seconds * 30.0) as usize {
+			physics.update(1.0 / 30.0);
+			let object = &physics[handle];
+			bounced |= object.vel.y > 0.0;
+			if bounced {
+				peak = peak.max(object.pos.y);
+			}
+		}
+		let object = &physics[handle];
+		let energy = physics.kinetic_energy() + gravity * object.pos.y;
+		(peak, energy / start_energy, object.vel.x)
+	}
+
+	#[test]
+	fn lighter_objects_get_pushed_further() {
+		let object = |x: f32, mass: f32| {
+			let mut physics = Physics::new(10, 10);
+			let handle = physics.add_object_with(x, 5.0, 0.5, mass, WHITE).unwrap();
+			physics[handle]
+		};
+		let (mut heavy, mut light) = (object(4.0, 10.0), object(4.6, 1.0));
+		heavy.check_collision(&mut light);
+		// 0.4 of overlap split 1 to 10
+		assert!((heavy.pos.x - (4.0 - 0.4 / 11.0)).abs() < 1e-5, "heavy moved to {}", heavy.pos.x);
+		assert!((light.pos.x - (4.6 + 4.0 / 11.0)).abs() < 1e-5, "light moved to {}", light.pos.x);
+		assert!((light.pos.x - heavy.pos.x - 1.0).abs() < 1e-5, "still overlapping");
+
+		let (mut a, mut b) = (object(4.0, 2.0), object(4.6, 2.0));
+		a.check_collision(&mut b);
+		assert!((4.0 - a.pos.x - (b.pos.x - 4.6)).abs() < 1e-5, "equal masses moved unequally");
+	}
+
+	#[test]
+	fn default_material_lands_without_bouncing_or_friction() {
+		let (peak, energy, x_speed) = drop(Material::DEFAULT, 1.5);
+		assert!(peak < 1.0, "bounced up to {}", peak);
+		assert!(energy < 0.05, "{} of the energy left", energy);
+		assert!((x_speed - 10.0).abs() < 1e-3, "slowed down to {}", x_speed);
+	}
+
+	#[test]
+	fn sand_stops_dead() {
+		let (peak, energy, x_speed) = drop(Material::SAND, 1.5);
+		assert!(peak < 1.0, "bounced up to {}", peak);
+		assert!(energy < 0.05, "{} of the energy left", energy);
+		assert!(x_speed.abs() < 1e-3, "still sliding at {}", x_speed);
+	}
+
+	#[test]
+	fn bouncy_rebounds_most_of_the_way() {
+		let (peak, energy, _) = drop(Material::BOUNCY, 5.0);
+		assert!(peak > 60.0 * 0.7 && peak < 60.0, "bounced up to {}", peak);
+		assert!(energy > 0.3 && energy <= 1.0, "{} of the energy left", energy);
+	}
+
+	#[test]
+	fn no_material_gains_energy() {
+		for material in [Material::DEFAULT, Material::SAND, Material::BOUNCY] {
+			for seconds in [0.5, 1.5, 3.0, 5.0] {
+				let (_, energy, _) = drop(material, seconds);
+				assert!(energy <= 1.0 + 1e-3, "{} of the energy after {}s", energy, seconds);
+			}
+		}
+	}
+
+	#[test]
+	fn friction_slows_sliding_objects() {
+		// sideways speed of an object sliding along the floor after a second
+		let slide = |material: Material| {
+			let (physics, handle) = launch(|physics| physics.config.default_material = material, glm::vec2(10.0, DEFAULT_RADIUS), glm::vec2(10.0, 0.0), 1.0);
+			physics[handle].vel.x
+		};
+		assert!((slide(Material::DEFAULT) - 10.0).abs() < 1e-3);
+		assert!(slide(Material { restitution: 0.0, friction: 0.5 }).abs() < 1e-3);
+		let light = slide(Material { restitution: 0.0, friction: 0.05 });
+		assert!(light > 0.0 && light < 10.0, "light friction left {}", light);
+	}
+
+	#[test]
+	fn air_drag_follows_quadratic_decay() {
+		let drag = |physics: &mut Physics| {
+			physics.config.gravity = glm::vec2(0.0, 0.0);
+			physics.config.air_drag = 0.1;
+		};
+		let (physics, handle) = launch(drag, glm::vec2(10.0, 34.0), glm::vec2(10.0, 0.0), 1.0);
+		// dv/dt = -k v^2 gives v = v0 / (1 + k v0 t)
+		let speed = physics[handle].vel.x;
+		assert!((speed - 5.0).abs() < 0.25, "expected about 5, got {}", speed);
+	}
+
+	#[test]
+	fn objects_rest_on_every_collider_shape() {
+		let floor = 20.0 + DEFAULT_RADIUS;
+		let cases = [
+			(Collider::Segment { a: glm::vec2(40.0, 20.0), b: glm::vec2(80.0, 20.0) }, floor),
+			(Collider::Capsule { a: glm::vec2(40.0, 19.0), b: glm::vec2(80.0, 19.0), radius: 1.0 }, floor),
+			(Collider::Circle { center: glm::vec2(60.0, 15.0), radius: 5.0 }, floor),
+			(Collider::rectangle(glm::vec2(40.0, 10.0), glm::vec2(80.0, 20.0)), floor)
+		];
+		for (collider, rest) in cases {
+			let (physics, handle) = launch(|physics| { physics.add_collider(collider); }, glm::vec2(60.0, 50.0), glm::vec2(0.0, 0.0), 3.0);
+			let pos = physics[handle].pos;
+			assert!((pos.x - 60.0).abs() < 1e-3 && (pos.y - rest).abs() < 0.05, "came to rest at ({}, {})", pos.x, pos.y);
+		}
+	}
+
+	#[test]
+	fn objects_slide_off_slopes() {
+		let slope = Collider::Segment { a: glm::vec2(20.0, 40.0), b: glm::vec2(100.0, 20.0) };
+		let (physics, handle) = launch(|physics| { physics.add_collider(slope); }, glm::vec2(60.0, 60.0), glm::vec2(0.0, 0.0), 3.0);
+		let pos = physics[handle].pos;
+		assert!(pos.x > 100.0 && pos.y < 2.0, "stuck at ({}, {})", pos.x, pos.y);
+	}
+
+	#[test]
+	fn adaptive_substeps_stop_fast_objects_tunnelling() {
+		// where an object fired at a thin wall is after half a second
+		let fire = |max_step_distance: Option<f32>| {
+			let wall = |physics: &mut Physics| {
+				physics.config.gravity = glm::vec2(0.0, 0.0);
+				physics.config.max_step_distance = max_step_distance;
+				physics.add_collider(Collider::Segment { a: glm::vec2(60.0, 10.0), b: glm::vec2(60.0, 58.0) });
+			};
+			let (physics, handle) = launch(wall, glm::vec2(40.3, 34.0), glm::vec2(300.0, 0.0), 0.5);
+			physics[handle].pos.x
+		};
+		// 8 substeps move it 1.25 at a time, past the wall's middle in one go
+		assert!(fire(None) > 60.0, "the test no longer tunnels without adaptive substeps");
+		let x = fire(Some(0.5));
+		assert!(x < 60.0, "tunnelled through to {}", x);
+	}
+
+	#[test]
+	fn polygons_must_be_convex() {
+		let square = vec![glm::vec2(0.0, 0.0), glm::vec2(1.0, 0.0), glm::vec2(1.0, 1.0), glm::vec2(0.0, 1.0)];
+		assert!(Collider::polygon(square.clone()).is_ok());
+		// clockwise gets turned around
+		match Collider::polygon(square.iter().rev().copied().collect()).unwrap() {
+			Collider::Polygon { points } => assert!(cross(points[1] - points[0], points[2] - points[1]) > 0.0),
+			_ => unreachable!()
+		}
+		assert!(Collider::polygon(vec![glm::vec2(0.0, 0.0), glm::vec2(1.0, 0.0)]).is_err());
+		assert!(Collider::polygon(vec![glm::vec2(0.0, 0.0), glm::vec2(2.0, 0.0), glm::vec2(1.0, 0.2), glm::vec2(2.0, 2.0), glm::vec2(0.0, 2.0)]).is_err());
+		assert!(Collider::polygon(vec![glm::vec2(0.0, 0.0), glm::vec2(1.0, 0.0), glm::vec2(2.0, 0.0)]).is_err());
+	}
+
+	// a world with only `force_field` pulling on objects
+	fn weightless(force_field: ForceField) -> impl FnOnce(&mut Physics) {
+		move |physics| {
+			physics.config.gravity = glm::vec2(0.0, 0.0);
+			physics.add_force_field(force_field);
+		}
+	}
+
+	#[test]
+	fn attractors_pull_and_repulsors_push() {
+		let center = glm::vec2(60.0, 34.0);
+		let start = glm::vec2(75.0, 34.0);
+		let attractor = ForceField::Point { position: center, strength: 20.0, radius: 30.0, falloff: Falloff::Linear };
+		let repulsor = ForceField::Point { position: center, strength: -20.0, radius: 30.0, falloff: Falloff::Linear };
+		let end = |force_field: ForceField| {
+			let (physics, handle) = launch(weightless(force_field), start, glm::vec2(0.0, 0.0), 1.0);
+			physics[handle].pos
+		};
+		assert!(glm::distance(&end(attractor), &center) < 15.0 - 1.0);
+		assert!(glm::distance(&end(repulsor), &center) > 15.0 + 1.0);
+		// nothing happens outside the radius
+		let small = ForceField::Point { position: center, strength: 20.0, radius: 10.0, falloff: Falloff::None };
+		assert!(end(small) == start);
+	}
+
+	#[test]
+	fn falloff_weakens_the_pull_with_distance() {
+		let position = glm::vec2(0.0, 0.0);
+		for falloff in [Falloff::Linear, Falloff::InverseSquare] {
+			let field = ForceField::Point { position, strength: 10.0, radius: 20.0, falloff };
+			let near = glm::length(&field.acceleration(glm::vec2(2.0, 0.0), 0.0));
+			let far = glm::length(&field.acceleration(glm::vec2(15.0, 0.0), 0.0));
+			assert!(near > far && far > 0.0, "near {}, far {}", near, far);
+		}
+		let flat = ForceField::Point { position, strength: 10.0, radius: 20.0, falloff: Falloff::None };
+		assert!((glm::length(&flat.acceleration(glm::vec2(15.0, 0.0), 0.0)) - 10.0).abs() < 1e-4);
+	}
+
+	#[test]
+	fn vortices_spin_objects_around_their_center() {
+		let center = glm::vec2(60.0, 34.0);
+		let start = glm::vec2(70.0, 34.0);
+		for strength in [20.0, -20.0] {
+			let vortex = ForceField::Vortex { position: center, strength, radius: 30.0 };
+			let (physics, handle) = launch(weightless(vortex), start, glm::vec2(0.0, 0.0), 1.0);
+			let object = physics[handle];
+			// angular momentum around the center, counter-clockwise is positive
+			let spin = cross(object.pos - center, object.vel);
+			assert!(spin * strength > 0.0, "strength {} gave spin {}", strength, spin);
+		}
+	}
+
+	#[test]
+	fn wind_gusts_stay_within_their_strength() {
+		let wind = ForceField::Wind { acceleration: glm::vec2(10.0, 0.0), gust_strength: 0.5, gust_frequency: 2.0, seed: 3 };
+		let mut strengths: Vec<f32> = Vec::new();
+		for i in 0..200 {
+			let acceleration = wind.acceleration(glm::vec2(0.0, 0.0), i as f32 * 0.05);
+			assert!(acceleration.y == 0.0);
+			strengths.push(acceleration.x);
+		}
+		let min = strengths.iter().copied().fold(f32::INFINITY, f32::min);
+		let max = strengths.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+		assert!(min >= 5.0 && max <= 15.0 && max - min > 1.0, "wind between {} and {}", min, max);
+		let (physics, handle) = launch(weightless(wind), glm::vec2(20.0, 34.0), glm::vec2(0.0, 0.0), 1.0);
+		assert!(physics[handle].pos.x > 20.0);
+	}
+
+	#[test]
+	fn turbulence_swirls_without_bunching_objects_up() {
+		let turbulence = ForceField::Turbulence { strength: 10.0, scale: 8.0, speed: 0.5, seed: 7 };
+		let epsilon = 0.01;
+		let mut largest: f32 = 0.0;
+		for i in 0..50 {
+			let p = glm::vec2(3.0 + i as f32 * 1.7, 5.0 + i as f32 * 0.9);
+			let acceleration = turbulence.acceleration(p, 1.0);
+			largest = largest.max(glm::length(&acceleration));
+			// the field is the curl of the noise, so nothing flows together or apart
+			let dx = turbulence.acceleration(p + glm::vec2(epsilon, 0.0), 1.0).x - turbulence.acceleration(p - glm::vec2(epsilon, 0.0), 1.0).x;
+			let dy = turbulence.acceleration(p + glm::vec2(0.0, epsilon), 1.0).y - turbulence.acceleration(p - glm::vec2(0.0, epsilon), 1.0).y;
+			let divergence = (dx + dy) / (2.0 * epsilon);
+			assert!(divergence.abs() < 0.05, "divergence {} at ({}, {})", divergence, p.x, p.y);
+		}
+		assert!(largest > 1.0);
+	}
+
+	#[test]
+	fn ropes_keep_their_length_and_pin() {
+		let mut physics = Physics::new(120, 68);
+		let rope = physics.add_rope(glm::vec2(30.0, 60.0), glm::vec2(60.0, 60.0), 15, 1.0, true, WHITE);
+		run(&mut physics, 3.0);
+		assert!(physics[rope[0]].pos == glm::vec2(30.0, 60.0), "the pin moved");
+		for pair in rope.windows(2) {
+			let length = glm::distance(&physics[pair[0]].pos, &physics[pair[1]].pos);
+			assert!((length - 2.0).abs() < 0.2, "a link stretched to {}", length);
+		}
+		// it swung down
+		assert!(physics[rope[15]].pos.y < 55.0);
+	}
+
+	#[test]
+	fn bending_keeps_ropes_straight() {
+		// lowest the end of a level rope gets
+		let lowest_end = |bend: Option<f32>| {
+			let mut physics = Physics::new(120, 68);
+			let rope = physics.add_rope(glm::vec2(30.0, 60.0), glm::vec2(45.0, 60.0), 6, 1.0, true, WHITE);
+			// a second pin next to the first holds the rope level at the start
+			physics.pin(rope[1]);
+			if let Some(stiffness) = bend {
+				physics.add_bending(&rope, stiffness);
+			}
+			let mut lowest: f32 = 60.0;
+			for _ in 0..60 {
+				physics.update(1.0 / 30.0);
+				lowest = lowest.min(physics[rope[6]].pos.y);
+			}
+			lowest
+		};
+		let (limp, stiff) = (lowest_end(None), lowest_end(Some(1.0)));
+		assert!(limp < 52.0, "limp rope only dropped to {}", limp);
+		assert!(stiff > 57.0, "stiff rope dropped to {}", stiff);
+	}
+
+	#[test]
+	fn links_break_past_their_threshold() {
+		let mut physics = Physics::new(120, 68);
+		physics.config.gravity = glm::vec2(0.0, 0.0);
+		let a = physics.add_object(50.0, 30.0, WHITE).unwrap();
+		let b = physics.add_object(52.0, 30.0, WHITE).unwrap();
+		physics.link(a, b, 1.0, Some(1.5));
+		physics.pin(a);
+
+		// pulled a little it springs back
+		physics[b].vel = glm::vec2(20.0, 0.0);
+		run(&mut physics, 0.5);
+		assert_eq!(physics.constraints().len(), 2);
+		assert!((glm::distance(&physics[a].pos, &physics[b].pos) - 2.0).abs() < 0.1);
+
+		// yanked away it snaps
+		physics[b].pos = glm::vec2(60.0, 30.0);
+		run(&mut physics, 0.1);
+		assert_eq!(physics.constraints().len(), 1);
+		assert!(glm::distance(&physics[a].pos, &physics[b].pos) > 5.0);
+	}
+
+	#[test]
+	fn soft_bodies_hold_their_area_under_pressure() {
+		let area_after_landing = |pressure: f32| {
+			let mut physics = Physics::new(120, 68);
+			let index = physics.add_soft_body(glm::vec2(60.0, 30.0), 8.0, 24, pressure, 0.5, WHITE);
+			let rest_area = physics.soft_bodies[index].rest_area;
+			run(&mut physics, 3.0);
+			physics.soft_bodies[index].area(&physics.objects) / rest_area
+		};
+		let full = area_after_landing(1.0);
+		let inflated = area_after_landing(1.3);
+		assert!(full > 0.85 && full < 1.1, "kept {} of its area", full);
+		assert!(inflated > full + 0.1, "inflated to {}, normal {}", inflated, full);
+	}
+
+	// a weightless world with one emitter in the middle
+	fn emitting(mode: EmitMode, max_count: Option<usize>) -> Physics {
+		let mut physics = Physics::new(120, 68);
+		physics.config.gravity = glm::vec2(0.0, 0.0);
+		let mut emitter = Emitter::new(glm::vec2(60.0, 34.0), glm::vec2(1.0, 0.0), 1);
+		emitter.mode = mode;
+		emitter.max_count = max_count;
+		physics.add_emitter(emitter);
+		physics
+	}
+
+	#[test]
+	fn continuous_emitters_spawn_their_rate_per_second() {
+		let mut physics = emitting(EmitMode::Continuous { rate: 10.0 }, None);
+		// 2.5 objects per update, the fractions carry over
+		let counts: Vec<usize> = (0..8).map(|_| {
+			physics.update(0.25);
+			physics.object_count()
+		}).collect();
+		assert_eq!(counts, vec![2, 5, 7, 10, 12, 15, 17, 20]);
+	}
+
+	#[test]
+	fn burst_emitters_spawn_their_count_every_interval() {
+		let mut physics = emitting(EmitMode::Burst { count: 5, interval: 1.0 }, None);
+		let counts: Vec<usize> = (0..9).map(|_| {
+			physics.update(0.25);
+			physics.object_count()
+		}).collect();
+		// one burst straight away, then one a second
+		assert_eq!(counts, vec![5, 5, 5, 10, 10, 10, 10, 15, 15]);
+	}
+
+	#[test]
+	fn emitters_stop_at_their_max_count() {
+		let mut physics = emitting(EmitMode::Continuous { rate: 100.0 }, Some(7));
+		run(&mut physics, 1.0);
+		assert_eq!(physics.object_count(), 7);
+
+		// removed objects make room for new ones
+		let handle = physics.emitters[0].alive[0];
+		physics.remove_object(handle);
+		assert_eq!(physics.object_count(), 6);
+		physics.update(1.0 / 30.0);
+		assert_eq!(physics.object_count(), 7);
+
+		let mut physics = emitting(EmitMode::Burst { count: 5, interval: 0.1 }, Some(7));
+		run(&mut physics, 1.0);
+		assert_eq!(physics.object_count(), 7);
+	}
+
+	#[test]
+	fn removed_slots_are_reused_and_old_handles_go_stale() {
+		let mut physics = Physics::new(120, 68);
+		let first = physics.add_object(10.0, 10.0, WHITE).unwrap();
+		let second = physics.add_object(20.0, 10.0, WHITE).unwrap();
+		assert!(physics.remove_object(first));
+		assert!(!physics.remove_object(first), "removed twice");
+		assert!(!physics.objects[first.index].is_alive());
+		assert!(physics.get(first).is_none());
+		assert_eq!(physics.object_count(), 1);
+
+		let third = physics.add_object(30.0, 10.0, WHITE).unwrap();
+		assert_eq!(third.index, first.index, "the free slot was not reused");
+		assert!(third.generation != first.generation);
+		assert!(physics.get(first).is_none(), "a stale handle reached the new object");
+		assert!(physics[third].pos == glm::vec2(30.0, 10.0));
+		assert!(physics[second].pos == glm::vec2(20.0, 10.0));
+		assert!(physics[first.index].pos == glm::vec2(30.0, 10.0));
+	}
+
+	#[test]
+	#[should_panic(expected = "was removed")]
+	fn indexing_a_removed_slot_panics() {
+		let mut physics = Physics::new(120, 68);
+		let handle = physics.add_object(10.0, 10.0, WHITE).unwrap();
+		physics.remove_object(handle);
+		let _ = physics[handle.index].pos;
+	}
+
+	#[test]
+	#[should_panic(expected = "stale physics handle")]
+	fn indexing_a_stale_handle_panics() {
+		let mut physics = Physics::new(120, 68);
+		let handle = physics.add_object(10.0, 10.0, WHITE).unwrap();
+		physics.remove_object(handle);
+		physics.add_object(10.0, 10.0, WHITE);
+		let _ = physics[handle].pos;
+	}
+
+	#[test]
+	fn population_limit_drops_the_oldest_or_refuses() {
+		let mut physics = Physics::new(120, 68);
+		physics.config.max_objects = Some(3);
+		let handles: Vec<Handle> = (0..5).map(|i| physics.add_object(10.0 + i as f32 * 5.0, 10.0, WHITE).unwrap()).collect();
+		assert_eq!(physics.object_count(), 3);
+		assert!(!physics.is_valid(handles[0]) && !physics.is_valid(handles[1]));
+		assert!(handles[2..].iter().all(|&handle| physics.is_valid(handle)));
+
+		physics.config.population_policy = PopulationPolicy::RefuseSpawn;
+		assert!(physics.add_object(60.0, 10.0, WHITE).is_none());
+		assert!(handles[2..].iter().all(|&handle| physics.is_valid(handle)));
+	}
+
+	#[test]
+	fn lifetimes_and_drains_despawn_objects() {
+		let mut physics = Physics::new(120, 68);
+		physics.config.default_lifetime = Some(0.5);
+		let short = physics.add_object(30.0, 30.0, WHITE).unwrap();
+		physics.config.default_lifetime = None;
+		let falling = physics.add_object(60.0, 30.0, WHITE).unwrap();
+		let kept = physics.add_object(90.0, 30.0, WHITE).unwrap();
+		physics.add_drain(glm::vec2(50.0, 0.0), glm::vec2(70.0, 5.0));
+		run(&mut physics, 0.3);
+		assert!(physics.is_valid(short));
+		run(&mut physics, 3.0);
+		assert!(!physics.is_valid(short), "outlived its lifetime");
+		assert!(!physics.is_valid(falling), "fell through the drain");
+		assert!(physics.is_valid(kept));
+		assert_eq!(physics.object_count(), 1);
+	}
+
+	#[test]
+	fn the_population_limit_never_drops_constrained_objects() {
+		let mut physics = Physics::new(120, 68);
+		physics.config.max_objects = Some(12);
+		let rope = physics.add_rope(glm::vec2(30.0, 60.0), glm::vec2(40.0, 60.0), 5, 1.0, true, WHITE);
+		let cloth = physics.add_cloth(glm::vec2(60.0, 60.0), &ClothConfig { columns: 2, rows: 2, ..ClothConfig::default() }, WHITE);
+		let constraints = physics.constraints().len();
+		// 10 constrained objects leave room for 2 free ones
+		let free: Vec<Handle> = (0..4).map(|i| physics.add_object(10.0 + i as f32 * 5.0, 10.0, WHITE).unwrap()).collect();
+		assert!(!physics.is_valid(free[0]) && !physics.is_valid(free[1]));
+		assert!(physics.is_valid(free[2]) && physics.is_valid(free[3]));
+
+		// with only constrained objects left there is nothing to drop
+		physics.config.max_objects = Some(10);
+		assert!(physics.add_object(50.0, 10.0, WHITE).is_none());
+		assert_eq!(physics.object_count(), 10);
+		assert!(rope.iter().chain(cloth.iter()).all(|&handle| physics.is_valid(handle)), "a constrained object was dropped");
+		assert_eq!(physics.constraints().len(), constraints);
+	}
+
+	#[test]
+	fn removing_an_object_drops_its_constraints() {
+		let mut physics = Physics::new(120, 68);
+		let rope = physics.add_rope(glm::vec2(30.0, 60.0), glm::vec2(40.0, 60.0), 5, 1.0, true, WHITE);
+		physics.add_bending(&rope, 0.5);
+		// 5 links, a pin and 4 angles
+		assert_eq!(physics.constraints().len(), 10);
+		physics.remove_object(rope[0]);
+		// the first link, the pin and the first angle go
+		assert_eq!(physics.constraints().len(), 7);
+		run(&mut physics, 1.0);
+	}
+
+	#[test]
+	fn deterministic_mode_matches_across_thread_counts() {
+		let run = |threads: usize| {
+			let mut physics = scattered(3000, 120, 68);
+			physics.config.threads = threads;
+			physics.config.deterministic = true;
+			for _ in 0..5 {
+				physics.update(1.0 / 30.0);
+			}
+			physics.objects.iter().map(|object| (object.pos.x.to_bits(), object.pos.y.to_bits())).collect::<Vec<(u32, u32)>>()
+		};
+		let reference = run(1);
+		for threads in [2, 4, 8] {
+			assert!(run(threads) == reference, "{} threads moved the objects differently", threads);
+		}
+	}
+
+	#[test]
+	fn grid_cells_overflow_into_chained_cells() {
+		let mut grid = Grid::new(glm::vec2(10.0, 10.0), 1.0);
+		// indices past 255 as well, three cells worth in one spot
+		let indices: Vec<u32> = (250..270).collect();
+		for &index in indices.iter() {
+			grid.insert(3, 3, index);
+		}
+		grid.insert(4, 3, 1000);
+		assert_eq!(grid.overflow.len(), 2);
+
+		let mut out = Vec::new();
+		grid.collect(3, 3, &mut out);
+		assert_eq!(out, indices);
+		out.clear();
+		grid.collect(4, 3, &mut out);
+		assert_eq!(out, vec![1000]);
+
+		grid.clear();
+		out.clear();
+		grid.collect(3, 3, &mut out);
+		assert!(out.is_empty() && grid.overflow.is_empty());
+	}
+
+	#[test]
+	fn crowded_cells_still_collide_every_object() {
+		let mut physics = Physics::new(10, 10);
+		let count = CELL_CAPACITY * 3;
+		let start: Vec<glm::Vec2> = (0..count).map(|i| {
+			let angle = i as f32 / count as f32 * std::f32::consts::TAU;
+			glm::vec2(5.5 + 0.3 * angle.cos(), 5.5 + 0.3 * angle.sin())
+		}).collect();
+		for pos in start.iter() {
+			physics.add_object(pos.x, pos.y, WHITE);
+		}
+		physics.apply_collisions();
+		for (object, pos) in physics.objects.iter().zip(start.iter()) {
+			assert!(object.pos != *pos, "an object in an overflow cell was never collided");
+		}
+	}
+
+	#[test]
+	fn grid_collisions_match_brute_force() {
+		// random positions where objects overlap in pairs far enough from everything else
+		// that pushing a pair apart never makes a new overlap, so the order pairs are
+		// resolved in can't change the result
+		let mut rng = StdRng::seed_from_u64(5);
+		let mut positions: Vec<glm::Vec2> = Vec::new();
+		let mut partnered: Vec<bool> = Vec::new();
+		while positions.len() < 600 {
+			let pos = glm::vec2(rng.gen_range(0.0..120.0), rng.gen_range(0.0..68.0));
+			let near: Vec<usize> = (0..positions.len()).filter(|&i| glm::distance(&positions[i], &pos) < 2.0).collect();
+			match near[..] {
+				[] => partnered.push(false),
+				[other] if !partnered[other] && glm::distance(&positions[other], &pos) < DEFAULT_RADIUS * 2.0 => {
+					partnered[other] = true;
+					partnered.push(true);
+				}
+				_ => continue
+			}
+			positions.push(pos);
+		}
+		assert!(partnered[256..].iter().any(|&partnered| partnered), "no overlaps past index 255");
+
+		let collide = |brute_force: bool| {
+			let mut physics = Physics::new(120, 68);
+			for pos in positions.iter() {
+				physics.add_object(pos.x, pos.y, WHITE);
+			}
+			if brute_force {
+				physics.apply_collisions_brute_force();
+			} else {
+				physics.apply_collisions();
+			}
+			physics.objects.iter().map(|object| (object.pos.x.to_bits(), object.pos.y.to_bits())).collect::<Vec<(u32, u32)>>()
+		};
+		let grid = collide(false);
+		assert!(grid == collide(true), "the grid missed or added pairs");
+		let moved = grid.iter().zip(positions.iter()).filter(|((x, y), pos)| f32::from_bits(*x) != pos.x || f32::from_bits(*y) != pos.y).count();
+		assert_eq!(moved, partnered.iter().filter(|&&partnered| partnered).count());
+	}
+}
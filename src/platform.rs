@@ -0,0 +1,265 @@
+use crate::monitor::Monitor;
+use crate::render::Surface;
+
+pub enum Event {
+	// only sent by backends with a window of their own
+	#[allow(dead_code)]
+	Quit,
+	MonitorAdded(Monitor),
+	MonitorRemoved(Monitor),
+	// same name, new position or resolution
+	MonitorChanged(Monitor),
+	// the drawing surface moved or changed size, sent after the monitor events that caused it
+	SurfaceChanged(Surface)
+}
+
+// remembers the last monitor list and turns changes to it into events, monitors are matched by name
+pub struct MonitorWatcher {
+	known: Vec<Monitor>
+}
+
+impl MonitorWatcher {
+	pub fn new(monitors: Vec<Monitor>) -> MonitorWatcher {
+		MonitorWatcher {
+			known: monitors
+		}
+	}
+
+	pub fn update(&mut self, monitors: Vec<Monitor>) -> Vec<Event> {
+		let mut events: Vec<Event> = Vec::new();
+		for monitor in self.known.iter() {
+			if !monitors.iter().any(|other| other.name == monitor.name) {
+				events.push(Event::MonitorRemoved(monitor.clone()));
+			}
+		}
+		for monitor in monitors.iter() {
+			match self.known.iter().find(|other| other.name == monitor.name) {
+				None => events.push(Event::MonitorAdded(monitor.clone())),
+				Some(other) if other != monitor => events.push(Event::MonitorChanged(monitor.clone())),
+				_ => {}
+			}
+		}
+		self.known = monitors;
+		events
+	}
+}
+
+// things that make drawing the wallpaper pointless or unwelcome
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct DesktopState {
+	// a fullscreen window covers the desktop
+	pub obscured: bool,
+	pub locked: bool,
+	pub on_battery: bool,
+	pub display_off: bool
+}
+
+pub trait Platform {
+	fn monitors(&mut self) -> Vec<Monitor>;
+	// creates the surface behind the desktop icons, with a current OpenGL context when `has_gl` is true
+	fn create_surface(&mut self) -> Result<Surface, String>;
+	fn has_gl(&self) -> bool;
+	// called before drawing `monitor`, for backends with a separate surface per monitor,
+	// false skips the monitor when its surface isn't ready
+	fn begin_output(&mut self, _monitor: &Monitor) -> bool {
+		true
+	}
+	fn present(&mut self);
+	fn poll_events(&mut self) -> Vec<Event>;
+	// backends report what they can detect, the rest stays false
+	fn desktop_state(&mut self) -> DesktopState {
+		DesktopState::default()
+	}
+	fn destroy(&mut self);
+}
+
+// fake platform without any windows, for running headless and in tests
+pub struct HeadlessPlatform {
+	pub monitors: Vec<Monitor>,
+	// reported as is, set it to fake a fullscreen app, lock screen, etc.
+	pub desktop_state: DesktopState,
+	events: Vec<Event>
+}
+
+impl HeadlessPlatform {
+	pub fn new(monitors: Vec<Monitor>) -> HeadlessPlatform {
+		HeadlessPlatform {
+			monitors,
+			desktop_state: DesktopState::default(),
+			events: Vec::new()
+		}
+	}
+
+	#[allow(dead_code)]
+	pub fn push_event(&mut self, event: Event) {
+		self.events.push(event);
+	}
+
+	// swaps the monitor list and queues the events a real backend would send
+	#[allow(dead_code)]
+	pub fn set_monitors(&mut self, monitors: Vec<Monitor>) {
+		let old_surface = Surface::bounding(&self.monitors);
+		let mut watcher = MonitorWatcher::new(std::mem::take(&mut self.monitors));
+		self.events.extend(watcher.update(monitors.clone()));
+		let surface = Surface::bounding(&monitors);
+		if surface != old_surface {
+			self.events.push(Event::SurfaceChanged(surface));
+		}
+		self.monitors = monitors;
+	}
+}
+
+impl Default for HeadlessPlatform {
+	fn default() -> Self {
+		HeadlessPlatform::new(vec![Monitor::new("headless", 0, 0, 1920, 1080)])
+	}
+}
+
+impl Platform for HeadlessPlatform {
+	fn monitors(&mut self) -> Vec<Monitor> {
+		self.monitors.clone()
+	}
+
+	fn create_surface(&mut self) -> Result<Surface, String> {
+		Ok(Surface::bounding(&self.monitors))
+	}
+
+	fn has_gl(&self) -> bool {
+		false
+	}
+
+	fn present(&mut self) {}
+
+	fn poll_events(&mut self) -> Vec<Event> {
+		std::mem::take(&mut self.events)
+	}
+
+	fn desktop_state(&mut self) -> DesktopState {
+		self.desktop_state
+	}
+
+	fn destroy(&mut self) {}
+}
+
+// true when a battery is present and no mains supply is online
+#[cfg(target_os = "linux")]
+#[allow(dead_code)]
+pub fn linux_on_battery() -> bool {
+	let entries = match std::fs::read_dir("/sys/class/power_supply") {
+		Ok(entries) => entries,
+		Err(_) => return false
+	};
+	let mut battery = false;
+	for entry in entries.flatten() {
+		let path = entry.path();
+		let read = |name: &str| std::fs::read_to_string(path.join(name)).map(|text| text.trim().to_string()).unwrap_or_default();
+		match read("type").as_str() {
+			"Mains" | "USB" if read("online") == "1" => return false,
+			"Battery" => battery = true,
+			_ => {}
+		}
+	}
+	battery
+}
+
+// the platform backend for the OS we were built for
+pub fn native() -> Result<Box<dyn Platform>, String> {
+	#[cfg(windows)]
+	{
+		Ok(Box::new(crate::window::Win32Platform::new()))
+	}
+	// prefer Wayland when both are built in and a compositor is running
+	#[cfg(all(unix, feature = "wayland"))]
+	{
+		if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+			return Ok(Box::new(crate::wayland::WaylandPlatform::new()?));
+		}
+	}
+	#[cfg(all(unix, feature = "x11"))]
+	{
+		// bare window managers show the root window, desktop environments cover it with their own
+		let target = if std::env::var_os("XDG_CURRENT_DESKTOP").is_some() { crate::x11::X11Target::DesktopWindow } else { crate::x11::X11Target::Root };
+		Ok(Box::new(crate::x11::X11Platform::new(target)?))
+	}
+	#[cfg(not(any(windows, all(unix, feature = "x11"))))]
+	{
+		Err("No desktop backend for this platform, use the render command to run headless.".to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn describe(events: Vec<Event>) -> Vec<String> {
+		events.iter().map(|event| match event {
+			Event::Quit => "quit".to_owned(),
+			Event::MonitorAdded(monitor) => format!("added {}", monitor.name),
+			Event::MonitorRemoved(monitor) => format!("removed {}", monitor.name),
+			Event::MonitorChanged(monitor) => format!("changed {} {}x{}", monitor.name, monitor.w, monitor.h),
+			Event::SurfaceChanged(surface) => format!("surface {},{} {}x{}", surface.x, surface.y, surface.width, surface.height)
+		}).collect()
+	}
+
+	fn left() -> Monitor {
+		Monitor::new("left", 0, 0, 1920, 1080)
+	}
+
+	fn right() -> Monitor {
+		Monitor::new("right", 1920, 0, 1920, 1080)
+	}
+
+	#[test]
+	fn plugging_in_a_monitor_grows_the_surface() {
+		let mut platform = HeadlessPlatform::new(vec![left()]);
+		platform.set_monitors(vec![left(), right()]);
+		assert_eq!(describe(platform.poll_events()), ["added right", "surface 0,0 3840x1080"]);
+		assert!(platform.poll_events().is_empty(), "events were sent twice");
+		assert!(platform.monitors() == vec![left(), right()]);
+	}
+
+	#[test]
+	fn unplugging_a_monitor_shrinks_the_surface() {
+		let mut platform = HeadlessPlatform::new(vec![left(), right()]);
+		platform.set_monitors(vec![right()]);
+		assert_eq!(describe(platform.poll_events()), ["removed left", "surface 1920,0 1920x1080"]);
+	}
+
+	#[test]
+	fn changing_resolution_keeps_the_monitor() {
+		let mut platform = HeadlessPlatform::new(vec![left(), right()]);
+		platform.set_monitors(vec![left(), Monitor::new("right", 1920, 0, 1280, 1024)]);
+		assert_eq!(describe(platform.poll_events()), ["changed right 1280x1024", "surface 0,0 3200x1080"]);
+
+		// a smaller monitor inside the bounding box leaves the surface alone
+		platform.set_monitors(vec![left(), Monitor::new("right", 1920, 0, 1280, 720)]);
+		assert_eq!(describe(platform.poll_events()), ["changed right 1280x720"]);
+	}
+
+	#[test]
+	fn swapping_monitors_sends_the_removal_first() {
+		let mut platform = HeadlessPlatform::new(vec![left()]);
+		platform.set_monitors(vec![Monitor::new("other", 0, 0, 1920, 1080)]);
+		assert_eq!(describe(platform.poll_events()), ["removed left", "added other"]);
+	}
+
+	#[test]
+	fn unchanged_monitors_send_nothing() {
+		let mut platform = HeadlessPlatform::new(vec![left(), right()]);
+		platform.set_monitors(vec![right(), left()]);
+		assert!(platform.poll_events().is_empty());
+
+		let mut watcher = MonitorWatcher::new(vec![left()]);
+		assert_eq!(describe(watcher.update(vec![left(), right()])), ["added right"]);
+		assert!(watcher.update(vec![left(), right()]).is_empty(), "the watcher forgot the new monitor");
+	}
+
+	#[test]
+	fn queued_events_come_out_in_order() {
+		let mut platform = HeadlessPlatform::new(vec![left()]);
+		platform.push_event(Event::Quit);
+		platform.set_monitors(vec![left(), right()]);
+		platform.set_monitors(vec![right()]);
+		assert_eq!(describe(platform.poll_events()), ["quit", "added right", "surface 0,0 3840x1080", "removed left", "surface 1920,0 1920x1080"]);
+	}
+}
@@ -0,0 +1,365 @@
+use crate::array2d::*;
+use crate::graphics::*;
+
+// a fullscreen pass over a monitor's picture once its particles are drawn
+#[derive(Clone)]
+pub enum PostEffect {
+	// what's brighter than `threshold` glows about `radius` pixels into its surroundings
+	Bloom { threshold: f32, intensity: f32, radius: f32 },
+	// darkens towards the corners, by `strength` in the corners themselves
+	Vignette { strength: f32 },
+	// pulls red and blue apart towards the edges, `offset` pixels in the corners
+	ChromaticAberration { offset: f32 },
+	// maps every colour through a lookup table
+	ColorGrading { lut: Lut },
+	// darkens every other band of `spacing` rows by `intensity`
+	Scanlines { intensity: f32, spacing: f32 }
+}
+
+// a 3D colour lookup table, red changes fastest
+#[derive(Clone)]
+pub struct Lut {
+	size: usize,
+	data: Vec<[f32; 3]>
+}
+
+impl Lut {
+	// an Adobe .cube file with a 3D table over the default 0 to 1 domain
+	pub fn load(path: &std::path::Path) -> Result<Lut, String> {
+		let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+		Lut::parse(&text).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+	}
+
+	fn parse(text: &str) -> Result<Lut, String> {
+		let mut size: Option<usize> = None;
+		let mut data: Vec<[f32; 3]> = Vec::new();
+		for (number, line) in text.lines().enumerate().map(|(number, line)| (number + 1, line.trim())) {
+			let mut words = line.split_whitespace();
+			match words.next() {
+				None => {},
+				Some(word) if word.starts_with('#') || word == "TITLE" => {},
+				Some("LUT_3D_SIZE") => {
+					let value = words.next().and_then(|word| word.parse::<usize>().ok()).filter(|size| *size >= 2);
+					size = Some(value.ok_or(format!("line {}: expected a size of at least 2", number))?);
+				},
+				Some("LUT_1D_SIZE") => return Err("only 3D tables are supported".to_string()),
+				Some(word @ ("DOMAIN_MIN" | "DOMAIN_MAX")) => {
+					let expected = if word == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+					if !words.all(|word| word.parse::<f32>() == Ok(expected)) {
+						return Err(format!("line {}: only the 0 to 1 domain is supported", number));
+					}
+				},
+				Some(_) => {
+					match line.split_whitespace().map(|word| word.parse::<f32>()).collect::<Result<Vec<f32>, _>>() {
+						Ok(values) if values.len() == 3 => data.push([values[0], values[1], values[2]]),
+						_ => return Err(format!("line {}: expected three numbers", number))
+					}
+				}
+			}
+		}
+
+		let size = size.ok_or("missing LUT_3D_SIZE".to_string())?;
+		if data.len() != size * size * size {
+			return Err(format!("expected {} entries, found {}", size * size * size, data.len()));
+		}
+		Ok(Lut { size, data })
+	}
+
+	// trilinear, like the GPU samples it
+	fn sample(&self, color: glm::Vec3) -> glm::Vec3 {
+		let n = self.size;
+		let position = glm::clamp(&color, 0.0, 1.0) * (n - 1) as f32;
+		let base = glm::vec3((position.x as usize).min(n - 2), (position.y as usize).min(n - 2), (position.z as usize).min(n - 2));
+		let fraction = position - glm::vec3(base.x as f32, base.y as f32, base.z as f32);
+		let mut result = glm::Vec3::zeros();
+		for corner in 0..8 {
+			let (r, g, b) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+			let weight = (if r == 1 { fraction.x } else { 1.0 - fraction.x })
+				* (if g == 1 { fraction.y } else { 1.0 - fraction.y })
+				* (if b == 1 { fraction.z } else { 1.0 - fraction.z });
+			let entry = self.data[(base.x + r) + (base.y + g) * n + (base.z + b) * n * n];
+			result += glm::vec3(entry[0], entry[1], entry[2]) * weight;
+		}
+		result
+	}
+}
+
+// the effects one after the other over `image`, the CPU version of PostChain
+pub fn apply(effects: &[PostEffect], image: &mut Array2D<Color>) {
+	if effects.is_empty() {
+		return;
+	}
+
+	let (w, h) = (image.width(), image.height());
+	let mut pixels: Array2D<glm::Vec3> = Array2D::new(w, h, glm::Vec3::zeros());
+	for y in 0..h {
+		for x in 0..w {
+			let color = image[(x, y)];
+			pixels[(x, y)] = glm::vec3(color.r as f32, color.g as f32, color.b as f32) / 255.0;
+		}
+	}
+
+	for effect in effects.iter() {
+		pixels = match effect {
+			PostEffect::Bloom { threshold, intensity, radius } => {
+				let bright = map_pixels(&pixels, |_, _, color| glm::vec3((color.x - threshold).max(0.0), (color.y - threshold).max(0.0), (color.z - threshold).max(0.0)));
+				let glow = blur(&blur(&bright, *radius, (1, 0)), *radius, (0, 1));
+				map_pixels(&pixels, |x, y, color| color + glow[(x, y)] * *intensity)
+			},
+			PostEffect::Vignette { strength } => map_pixels(&pixels, |x, y, color| {
+				let offset = glm::vec2((x as f32 + 0.5) / w as f32 - 0.5, (y as f32 + 0.5) / h as f32 - 0.5);
+				color * (1.0 - strength * glm::dot(&offset, &offset) * 2.0).clamp(0.0, 1.0)
+			}),
+			PostEffect::ChromaticAberration { offset } => map_pixels(&pixels, |x, y, color| {
+				let shift_x = (((x as f32 + 0.5) / w as f32 - 0.5) * 2.0 * offset).round() as i32;
+				let shift_y = (((y as f32 + 0.5) / h as f32 - 0.5) * 2.0 * offset).round() as i32;
+				let red = clamped(&pixels, x as i32 + shift_x, y as i32 + shift_y).x;
+				let blue = clamped(&pixels, x as i32 - shift_x, y as i32 - shift_y).z;
+				glm::vec3(red, color.y, blue)
+			}),
+			PostEffect::ColorGrading { lut } => map_pixels(&pixels, |_, _, color| lut.sample(color)),
+			PostEffect::Scanlines { intensity, spacing } => map_pixels(&pixels, |_, y, color| {
+				let dark = ((y as f32 + 0.5) / spacing) % 2.0 >= 1.0;
+				if dark { color * (1.0 - intensity) } else { color }
+			})
+		};
+	}
+
+	for y in 0..h {
+		for x in 0..w {
+			let color = glm::clamp(&pixels[(x, y)], 0.0, 1.0) * 255.0;
+			image[(x, y)] = Color { r: color.x as u8, g: color.y as u8, b: color.z as u8, a: image[(x, y)].a };
+		}
+	}
+}
+
+fn map_pixels(pixels: &Array2D<glm::Vec3>, f: impl Fn(usize, usize, glm::Vec3) -> glm::Vec3) -> Array2D<glm::Vec3> {
+	let mut result: Array2D<glm::Vec3> = Array2D::new(pixels.width(), pixels.height(), glm::Vec3::zeros());
+	for y in 0..pixels.height() {
+		for x in 0..pixels.width() {
+			result[(x, y)] = f(x, y, pixels[(x, y)]);
+		}
+	}
+	result
+}
+
+// the edges repeat, like the frame buffers clamp
+fn clamped(pixels: &Array2D<glm::Vec3>, x: i32, y: i32) -> glm::Vec3 {
+	pixels[(x.clamp(0, pixels.width() as i32 - 1) as usize, y.clamp(0, pixels.height() as i32 - 1) as usize)]
+}
+
+// one direction of the same gaussian the bloom shaders use
+fn blur(pixels: &Array2D<glm::Vec3>, radius: f32, direction: (i32, i32)) -> Array2D<glm::Vec3> {
+	let taps: Vec<(i32, f32)> = (-8..=8).map(|i: i32| (((i as f32) * radius / 8.0).round() as i32, (-2.0 * (i * i) as f32 / 64.0).exp())).collect();
+	let total: f32 = taps.iter().map(|(_, weight)| weight).sum();
+	map_pixels(pixels, |x, y, _| {
+		let mut sum = glm::Vec3::zeros();
+		for (offset, weight) in taps.iter() {
+			sum += clamped(pixels, x as i32 + offset * direction.0, y as i32 + offset * direction.1) * *weight;
+		}
+		sum / total
+	})
+}
+
+// the shaders of every kind of pass, they share the texture vertex shader
+pub struct PostShaders {
+	bloom_blur: shader::Shader,
+	bloom: shader::Shader,
+	vignette: shader::Shader,
+	chromatic_aberration: shader::Shader,
+	color_grading: shader::Shader,
+	scanlines: shader::Shader
+}
+
+impl PostShaders {
+	pub fn load() -> PostShaders {
+		PostShaders {
+			bloom_blur: shader::load_with("texture", "post_bloom_blur"),
+			bloom: shader::load_with("texture", "post_bloom"),
+			vignette: shader::load_with("texture", "post_vignette"),
+			chromatic_aberration: shader::load_with("texture", "post_chromatic_aberration"),
+			color_grading: shader::load_with("texture", "post_color_grading"),
+			scanlines: shader::load_with("texture", "post_scanlines")
+		}
+	}
+}
+
+// a monitor's post-processing on the GPU, the monitor is drawn into `input`, every pass but the
+// last renders into one of `buffers` and the last one onto the screen
+pub struct PostChain {
+	effects: Vec<PostEffect>,
+	pub input: FrameBuffer,
+	buffers: Vec<FrameBuffer>,
+	// the horizontally blurred bright parts for bloom
+	bloom: Option<FrameBuffer>,
+	// one per colour grading pass, in order
+	luts: Vec<LutTexture>
+}
+
+impl PostChain {
+	pub fn new(effects: &[PostEffect], width: i32, height: i32) -> Result<PostChain, String> {
+		let mut buffers: Vec<FrameBuffer> = Vec::new();
+		for _ in 0..(effects.len() - 1).min(2) {
+			buffers.push(FrameBuffer::new(width, height)?);
+		}
+		let bloom = match effects.iter().any(|effect| matches!(effect, PostEffect::Bloom { .. })) {
+			true => Some(FrameBuffer::new(width, height)?),
+			false => None
+		};
+		let luts = effects.iter().filter_map(|effect| match effect {
+			PostEffect::ColorGrading { lut } => Some(LutTexture::new(lut.size as i32, &lut.data)),
+			_ => None
+		}).collect();
+
+		Ok(PostChain {
+			effects: effects.to_vec(),
+			input: FrameBuffer::new(width, height)?,
+			buffers,
+			bloom,
+			luts
+		})
+	}
+
+	// runs the passes over `input`, `screen_quad` covers a frame buffer and `monitor_rect` the monitor
+	// on screen, where the last pass goes with `viewport`
+	pub fn run(&self, shaders: &PostShaders, screen_quad: &Rect, monitor_rect: &Rect, viewport: [i32; 4]) {
+		let mut source: &Texture = &self.input.texture;
+		let mut luts = self.luts.iter();
+		for (i, effect) in self.effects.iter().enumerate() {
+			if let PostEffect::Bloom { threshold, radius, .. } = effect {
+				self.bloom.as_ref().unwrap().bind();
+				shaders.bloom_blur.bind();
+				shaders.bloom_blur.set_uniform1f("u_threshold", *threshold);
+				shaders.bloom_blur.set_uniform1f("u_radius", *radius);
+				source.bind();
+				screen_quad.draw();
+			}
+
+			let last = i + 1 == self.effects.len();
+			if last {
+				unsafe {
+					gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+					gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+				}
+			} else {
+				self.buffers[i % 2].bind();
+			}
+
+			// the second texture of a pass goes in unit 1
+			match effect {
+				PostEffect::Bloom { intensity, radius, .. } => {
+					shaders.bloom.bind();
+					shaders.bloom.set_uniform1f("u_intensity", *intensity);
+					shaders.bloom.set_uniform1f("u_radius", *radius);
+					shaders.bloom.set_uniform1i("u_bloom", 1);
+					unsafe { gl::ActiveTexture(gl::TEXTURE1) };
+					self.bloom.as_ref().unwrap().texture.bind();
+				},
+				PostEffect::Vignette { strength } => {
+					shaders.vignette.bind();
+					shaders.vignette.set_uniform1f("u_strength", *strength);
+				},
+				PostEffect::ChromaticAberration { offset } => {
+					shaders.chromatic_aberration.bind();
+					shaders.chromatic_aberration.set_uniform1f("u_offset", *offset);
+				},
+				PostEffect::ColorGrading { .. } => {
+					shaders.color_grading.bind();
+					shaders.color_grading.set_uniform1i("u_lut", 1);
+					unsafe { gl::ActiveTexture(gl::TEXTURE1) };
+					luts.next().unwrap().bind();
+				},
+				PostEffect::Scanlines { intensity, spacing } => {
+					shaders.scanlines.bind();
+					shaders.scanlines.set_uniform1f("u_intensity", *intensity);
+					shaders.scanlines.set_uniform1f("u_spacing", *spacing);
+				}
+			}
+			unsafe { gl::ActiveTexture(gl::TEXTURE0) };
+			source.bind();
+
+			if last {
+				monitor_rect.draw();
+			} else {
+				screen_quad.draw();
+				source = &self.buffers[i % 2].texture;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const IDENTITY: &str = "# identity\nTITLE \"identity\"\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 1 1 1\n\n0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+
+	fn frame(width: usize, height: usize, color: Color) -> Array2D<Color> {
+		Array2D::new(width, height, color)
+	}
+
+	fn red(image: &Array2D<Color>) -> Vec<u8> {
+		(0..image.height()).flat_map(|y| (0..image.width()).map(move |x| (x, y))).map(|(x, y)| image[(x, y)].r).collect()
+	}
+
+	#[test]
+	fn identity_cube_files_parse() {
+		let lut = Lut::parse(IDENTITY).unwrap();
+		assert_eq!(lut.size, 2);
+		// red changes fastest
+		assert_eq!(lut.data[1], [1.0, 0.0, 0.0]);
+		assert_eq!(lut.data[2], [0.0, 1.0, 0.0]);
+		assert_eq!(lut.data[4], [0.0, 0.0, 1.0]);
+		let color = glm::vec3(0.25, 0.5, 0.75);
+		assert!(glm::distance(&lut.sample(color), &color) < 1e-5);
+	}
+
+	#[test]
+	fn malformed_cube_files_say_what_is_wrong() {
+		let error = |text: &str| Lut::parse(text).err().unwrap();
+		assert_eq!(error("0 0 0\n"), "missing LUT_3D_SIZE");
+		assert_eq!(error("LUT_3D_SIZE 1\n"), "line 1: expected a size of at least 2");
+		assert_eq!(error("LUT_1D_SIZE 16\n"), "only 3D tables are supported");
+		assert_eq!(error("LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n"), "line 2: only the 0 to 1 domain is supported");
+		assert_eq!(error("LUT_3D_SIZE 2\n0 0\n"), "line 2: expected three numbers");
+		assert_eq!(error("LUT_3D_SIZE 2\n0 0 zero\n"), "line 2: expected three numbers");
+		assert_eq!(error(IDENTITY.trim_end_matches("1 1 1\n")), "expected 8 entries, found 7");
+	}
+
+	#[test]
+	fn the_identity_lut_keeps_colours() {
+		let colors = [Color { r: 200, g: 100, b: 50, a: 255 }, Color { r: 0, g: 255, b: 17, a: 255 }, Color { r: 255, g: 255, b: 255, a: 128 }];
+		let mut image = frame(3, 1, Color::BLACK);
+		for (x, color) in colors.iter().enumerate() {
+			image[(x, 0)] = *color;
+		}
+		apply(&[PostEffect::ColorGrading { lut: Lut::parse(IDENTITY).unwrap() }], &mut image);
+		for (x, color) in colors.iter().enumerate() {
+			let graded = image[(x, 0)];
+			for (a, b) in [(graded.r, color.r), (graded.g, color.g), (graded.b, color.b)] {
+				assert!(a.abs_diff(b) <= 1, "{} became {}", b, a);
+			}
+			assert_eq!(graded.a, color.a);
+		}
+	}
+
+	#[test]
+	fn vignette_darkens_towards_the_corners() {
+		let mut image = frame(3, 3, Color { r: 255, g: 255, b: 255, a: 255 });
+		apply(&[PostEffect::Vignette { strength: 1.0 }], &mut image);
+		// 1 - 2 * distance² from the centre, a third of the frame out along each axis
+		assert_eq!(red(&image), [141, 198, 141, 198, 255, 198, 141, 198, 141]);
+	}
+
+	#[test]
+	fn scanlines_darken_every_other_band() {
+		let white = Color { r: 255, g: 255, b: 255, a: 255 };
+		let mut image = frame(1, 4, white);
+		apply(&[PostEffect::Scanlines { intensity: 0.5, spacing: 1.0 }], &mut image);
+		assert_eq!(red(&image), [255, 127, 255, 127]);
+
+		let mut image = frame(1, 4, white);
+		apply(&[PostEffect::Scanlines { intensity: 1.0, spacing: 2.0 }], &mut image);
+		assert_eq!(red(&image), [255, 255, 0, 0]);
+	}
+}
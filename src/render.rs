@@ -0,0 +1,778 @@
+use std::io::Write;
+
+use crate::array2d::*;
+use crate::graphics::*;
+use crate::monitor::Monitor;
+use crate::physics::Physics;
+use crate::post::*;
+
+// area of the virtual desktop covered by the drawing surface
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Surface {
+	pub x: i32,
+	pub y: i32,
+	pub width: i32,
+	pub height: i32
+}
+
+impl Surface {
+	pub fn bounding(monitors: &[Monitor]) -> Surface {
+		if monitors.is_empty() {
+			return Surface { x: 0, y: 0, width: 0, height: 0 };
+		}
+		let left = monitors.iter().map(|monitor| monitor.x).min().unwrap();
+		let top = monitors.iter().map(|monitor| monitor.y).min().unwrap();
+		let right = monitors.iter().map(|monitor| monitor.x + monitor.w).max().unwrap();
+		let bottom = monitors.iter().map(|monitor| monitor.y + monitor.h).max().unwrap();
+		Surface { x: left, y: top, width: right - left, height: bottom - top }
+	}
+}
+
+// how a scene's particles get on screen
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DrawMode {
+	// a texel per simulation cell, scaled up to the monitor
+	Texture,
+	// smoothed circles at the monitor's resolution
+	Circles
+}
+
+// what a monitor shows this frame, filled in from its simulation
+pub enum MonitorFrame {
+	// row 0 is the bottom of the monitor
+	Texture(Array2D<Color>),
+	// in simulation units, for a simulation of `size`
+	Circles { circles: Vec<Circle>, size: glm::Vec2 }
+}
+
+impl MonitorFrame {
+	pub fn new(mode: DrawMode, width: usize, height: usize) -> MonitorFrame {
+		match mode {
+			DrawMode::Texture => MonitorFrame::Texture(Array2D::new(width, height, Color::BLACK)),
+			DrawMode::Circles => MonitorFrame::Circles { circles: Vec::new(), size: glm::vec2(width as f32, height as f32) }
+		}
+	}
+
+	// the simulation's objects `alpha` of the way through its last update
+	pub fn fill(&mut self, simulation: &Physics, alpha: f32) {
+		match self {
+			MonitorFrame::Texture(texture_data) => simulation.render_interpolated(texture_data, alpha),
+			MonitorFrame::Circles { circles, size } => {
+				simulation.circles(circles, alpha);
+				*size = simulation.size;
+			}
+		}
+	}
+
+	pub fn clear(&mut self) {
+		match self {
+			MonitorFrame::Texture(texture_data) => {
+				for color in texture_data.as_mut_vec().iter_mut() {
+					*color = Color::BLACK;
+				}
+			},
+			MonitorFrame::Circles { circles, .. } => circles.clear()
+		}
+	}
+}
+
+// particles leave trails that fade out instead of the monitor being cleared every frame
+#[derive(Clone, Copy)]
+pub struct Trails {
+	// exponential decay per second
+	pub fade_rate: f32,
+	// adds particles onto the trail instead of keeping the brighter of the two
+	pub additive: bool,
+	pub blur: bool
+}
+
+impl Trails {
+	// fraction of the trail left after `dt` seconds
+	fn keep(&self, dt: f32) -> f32 {
+		(-self.fade_rate * dt).exp()
+	}
+
+	// adds `amount` of `src` onto the trail's `dst` or keeps the brighter of the two
+	fn blend(&self, dst: Color, src: Color, amount: f32) -> Color {
+		let channel = |dst: u8, src: u8| {
+			let src = src as f32 * amount;
+			if self.additive { (dst as f32 + src).min(255.0) as u8 } else { dst.max(src as u8) }
+		};
+		Color { r: channel(dst.r, src.r), g: channel(dst.g, src.g), b: channel(dst.b, src.b), a: dst.a }
+	}
+}
+
+// what happens to a monitor's picture besides drawing the particles
+#[derive(Clone, Default)]
+pub struct Effects {
+	pub trails: Option<Trails>,
+	// run in order once the particles are drawn
+	pub post: Vec<PostEffect>
+}
+
+pub trait Renderer {
+	// `dt` is the time since the last frame, which trails fade by
+	fn begin_frame(&mut self, dt: f32);
+	// draws the texture data for monitor `index` over that monitor
+	fn draw(&mut self, index: usize, data: &Array2D<Color>);
+	// draws circles in simulation units over monitor `index`, `size` being the simulation's
+	fn draw_circles(&mut self, index: usize, circles: &[Circle], size: glm::Vec2);
+	fn end_frame(&mut self) -> Result<(), String>;
+}
+
+// a monitor's trail, every frame the last one is faded into the other buffer and the particles drawn on top
+struct TrailBuffers {
+	trails: Trails,
+	buffers: [FrameBuffer; 2],
+	current: usize
+}
+
+pub struct GlRenderer {
+	textures: Vec<Texture>,
+	rectangles: Vec<Rect>,
+	// every monitor in clip space as left, bottom, right, top, and its width in pixels
+	placements: Vec<([f32; 4], f32)>,
+	trails: Vec<Option<TrailBuffers>>,
+	// monitors with post-processing are drawn into their chain's input instead of the screen
+	post: Vec<Option<PostChain>>,
+	// covers whatever frame buffer is bound
+	screen_quad: Rect,
+	shader: shader::Shader,
+	circle_buffer: CircleBuffer,
+	circle_shader: shader::Shader,
+	trail_shader: shader::Shader,
+	// only loaded when a monitor has post-processing
+	post_shaders: Option<PostShaders>,
+	dt: f32
+}
+
+impl GlRenderer {
+	pub fn new(surface: &Surface, monitors: &[Monitor], frames: &[MonitorFrame], effects: &[Effects], clear_color: Color) -> Result<GlRenderer, String> {
+		let mut textures: Vec<Texture> = Vec::<Texture>::with_capacity(monitors.len());
+		let mut rectangles: Vec<Rect> = Vec::<Rect>::with_capacity(monitors.len());
+		for (monitor, frame) in monitors.iter().zip(frames.iter()) {
+			match frame {
+				MonitorFrame::Texture(texture_data) => textures.push(Texture::new(texture_data.width() as i32, texture_data.height() as i32)),
+				// the circles go over black, the same as the texture path
+				MonitorFrame::Circles { .. } => {
+					let texture = Texture::new(1, 1);
+					texture.set_data(&Array2D::new(1, 1, Color::BLACK));
+					textures.push(texture);
+				}
+			}
+			rectangles.push(Rect::new(monitor.x as f32, monitor.y as f32, monitor.w as f32, monitor.h as f32, surface.x as f32, surface.y as f32, surface.width as f32, surface.height as f32));
+		}
+
+		// same mapping Rect does
+		let (x_w, y_w, w_w, h_w) = (surface.x as f32, surface.y as f32, surface.width as f32, surface.height as f32);
+		let placements = monitors.iter().map(|monitor| {
+			let left = map(x_w, x_w + w_w, -1.0, 1.0, monitor.x as f32);
+			let right = map(x_w, x_w + w_w, -1.0, 1.0, (monitor.x + monitor.w) as f32);
+			let bottom = map(y_w, y_w + h_w, 1.0, -1.0, (monitor.y + monitor.h) as f32);
+			let top = map(y_w, y_w + h_w, 1.0, -1.0, monitor.y as f32);
+			([left, bottom, right, top], monitor.w as f32)
+		}).collect();
+
+		// trails start out black, binding their buffers changes the viewport so it's put back afterwards
+		let viewport = current_viewport();
+		unsafe { gl::ClearColor(0.0, 0.0, 0.0, 1.0) };
+		let mut trail_buffers: Vec<Option<TrailBuffers>> = Vec::with_capacity(monitors.len());
+		for (monitor, effects) in monitors.iter().zip(effects.iter()) {
+			trail_buffers.push(match effects.trails.as_ref() {
+				Some(trails) => {
+					let buffers = [FrameBuffer::new(monitor.w.max(1), monitor.h.max(1))?, FrameBuffer::new(monitor.w.max(1), monitor.h.max(1))?];
+					for buffer in buffers.iter() {
+						buffer.bind();
+						unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) };
+					}
+					Some(TrailBuffers { trails: *trails, buffers, current: 0 })
+				},
+				None => None
+			});
+		}
+		unsafe {
+			gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+			gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+		}
+
+		let mut post: Vec<Option<PostChain>> = Vec::with_capacity(monitors.len());
+		for (monitor, effects) in monitors.iter().zip(effects.iter()) {
+			post.push(match effects.post.is_empty() {
+				true => None,
+				false => Some(PostChain::new(&effects.post, monitor.w.max(1), monitor.h.max(1))?)
+			});
+		}
+		let post_shaders = match post.iter().any(|chain| chain.is_some()) {
+			true => Some(PostShaders::load()),
+			false => None
+		};
+
+		let circle_shader: shader::Shader = shader::load("circle");
+		let trail_shader: shader::Shader = shader::load_with("texture", "trail");
+		let shader: shader::Shader = shader::load("texture");
+		shader.bind();
+
+		unsafe { gl::ClearColor(clear_color.r as f32 / 255.0, clear_color.g as f32 / 255.0, clear_color.b as f32 / 255.0, 1.0) };
+
+		Ok(GlRenderer {
+			textures,
+			rectangles,
+			placements,
+			trails: trail_buffers,
+			post,
+			screen_quad: Rect::new(0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0),
+			shader,
+			circle_buffer: CircleBuffer::new(),
+			circle_shader,
+			trail_shader,
+			post_shaders,
+			dt: 0.0
+		})
+	}
+
+	// binds what monitor `index` is drawn into, with `viewport` being the screen's, and returns
+	// whether that's offscreen
+	fn bind_target(&self, index: usize, viewport: [i32; 4]) -> bool {
+		match self.post[index].as_ref() {
+			Some(chain) => {
+				chain.input.bind();
+				true
+			},
+			None => {
+				unsafe {
+					gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+					gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+				}
+				false
+			}
+		}
+	}
+
+	// draws `texture` over all of monitor `index` in its target
+	fn draw_over_monitor(&self, index: usize, texture: &Texture) {
+		self.shader.bind();
+		texture.bind();
+		match self.post[index].is_some() {
+			true => self.screen_quad.draw(),
+			false => self.rectangles[index].draw()
+		}
+	}
+
+	// runs monitor `index`'s post-processing onto the screen
+	fn post_process(&self, index: usize, viewport: [i32; 4]) {
+		if let (Some(chain), Some(shaders)) = (self.post[index].as_ref(), self.post_shaders.as_ref()) {
+			chain.run(shaders, &self.screen_quad, &self.rectangles[index], viewport);
+		}
+	}
+
+	// fades monitor `index`'s trail into its other buffer and leaves that bound for the particles
+	fn begin_trail(&mut self, index: usize) {
+		let trail = self.trails[index].as_ref().unwrap();
+		let (source, target) = (&trail.buffers[trail.current], &trail.buffers[1 - trail.current]);
+		target.bind();
+		self.trail_shader.bind();
+		self.trail_shader.set_uniform1f("u_keep", trail.trails.keep(self.dt));
+		if trail.trails.blur {
+			self.trail_shader.set_uniform2f("u_texel", 1.0 / source.texture.width as f32, 1.0 / source.texture.height as f32);
+		} else {
+			self.trail_shader.set_uniform2f("u_texel", 0.0, 0.0);
+		}
+		source.texture.bind();
+		self.screen_quad.draw();
+
+		// particles add onto the trail or keep the brighter of the two
+		unsafe {
+			gl::Enable(gl::BLEND);
+			if trail.trails.additive {
+				gl::BlendFunc(gl::ONE, gl::ONE);
+			} else {
+				gl::BlendEquation(gl::MAX);
+			}
+		}
+	}
+
+	// puts the finished trail over monitor `index` in its target
+	fn end_trail(&mut self, index: usize, viewport: [i32; 4]) {
+		unsafe {
+			gl::BlendEquation(gl::FUNC_ADD);
+			gl::Disable(gl::BLEND);
+		}
+
+		let trail = self.trails[index].as_mut().unwrap();
+		trail.current = 1 - trail.current;
+		self.bind_target(index, viewport);
+		let trail = self.trails[index].as_ref().unwrap();
+		self.draw_over_monitor(index, &trail.buffers[trail.current].texture);
+	}
+
+	// the circles over `rect` in clip space, `width` pixels wide
+	fn draw_circle_instances(&self, circles: &[Circle], size: glm::Vec2, rect: [f32; 4], width: f32) {
+		self.circle_shader.bind();
+		self.circle_shader.set_uniform2f("u_size", size.x, size.y);
+		self.circle_shader.set_uniform4f("u_rect", rect[0], rect[1], rect[2], rect[3]);
+		self.circle_shader.set_uniform1f("u_scale", width / size.x);
+		self.circle_buffer.draw(circles);
+	}
+}
+
+impl Renderer for GlRenderer {
+	fn begin_frame(&mut self, dt: f32) {
+		self.dt = dt;
+		self.shader.bind();
+		unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) };
+	}
+
+	fn draw(&mut self, index: usize, data: &Array2D<Color>) {
+		self.textures[index].set_data(data);
+		let viewport = current_viewport();
+		self.bind_target(index, viewport);
+		if self.trails[index].is_some() {
+			self.begin_trail(index);
+			self.shader.bind();
+			self.textures[index].bind();
+			self.screen_quad.draw();
+			self.end_trail(index, viewport);
+		} else {
+			self.draw_over_monitor(index, &self.textures[index]);
+		}
+		self.post_process(index, viewport);
+	}
+
+	fn draw_circles(&mut self, index: usize, circles: &[Circle], size: glm::Vec2) {
+		let (rect, width) = self.placements[index];
+		let viewport = current_viewport();
+		let offscreen = self.bind_target(index, viewport);
+		if self.trails[index].is_some() {
+			self.begin_trail(index);
+			self.draw_circle_instances(circles, size, [-1.0, -1.0, 1.0, 1.0], width);
+			self.end_trail(index, viewport);
+			self.post_process(index, viewport);
+			return;
+		}
+
+		self.draw_over_monitor(index, &self.textures[index]);
+		unsafe {
+			// circles on the edge would spill onto the neighbouring monitor, the viewport
+			// may be shifted to an output so clip to where the monitor ends up in it
+			if !offscreen {
+				let [left, bottom, right, top] = rect;
+				let to_x = |x: f32| viewport[0] as f32 + (x + 1.0) * 0.5 * viewport[2] as f32;
+				let to_y = |y: f32| viewport[1] as f32 + (y + 1.0) * 0.5 * viewport[3] as f32;
+				gl::Scissor(to_x(left).round() as i32, to_y(bottom).round() as i32, (to_x(right) - to_x(left)).round() as i32, (to_y(top) - to_y(bottom)).round() as i32);
+				gl::Enable(gl::SCISSOR_TEST);
+			}
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+		}
+		self.draw_circle_instances(circles, size, if offscreen { [-1.0, -1.0, 1.0, 1.0] } else { rect }, width);
+		unsafe {
+			gl::Disable(gl::BLEND);
+			gl::Disable(gl::SCISSOR_TEST);
+		}
+		self.post_process(index, viewport);
+	}
+
+	fn end_frame(&mut self) -> Result<(), String> {
+		Ok(())
+	}
+}
+
+// the viewport the platform set up for the current output
+fn current_viewport() -> [i32; 4] {
+	let mut viewport: [i32; 4] = [0; 4];
+	unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()) };
+	viewport
+}
+
+// mixes `amount` of `src` into `dst`
+fn blend_over(dst: Color, src: Color, amount: f32) -> Color {
+	let channel = |dst: u8, src: u8| (dst as f32 + (src as f32 - dst as f32) * amount) as u8;
+	Color { r: channel(dst.r, src.r), g: channel(dst.g, src.g), b: channel(dst.b, src.b), a: dst.a }
+}
+
+// rasterises the circles into `rect` of `target`, `blend` mixes a circle's colour into a pixel by how much it covers it
+fn rasterize_circles(target: &mut Array2D<Color>, rect: (i32, i32, i32, i32), circles: &[Circle], size: glm::Vec2, blend: impl Fn(Color, Color, f32) -> Color) {
+	let (x, y, w, h) = rect;
+	let (x_start, x_end) = (x.max(0), (x + w).min(target.width() as i32));
+	let (y_start, y_end) = (y.max(0), (y + h).min(target.height() as i32));
+
+	// simulation y points up, frame rows go down
+	let scale = glm::vec2(w as f32 / size.x, h as f32 / size.y);
+	for circle in circles.iter() {
+		let center = glm::vec2(x as f32 + circle.x * scale.x, (y + h) as f32 - circle.y * scale.y);
+		let radius = circle.radius * scale.x;
+		let opacity = circle.color.a as f32 / 255.0;
+		// a pixel of margin for the smoothed edge, clipped to the rect
+		for p_y in ((center.y - radius - 1.0).floor() as i32).max(y_start)..((center.y + radius + 1.0).ceil() as i32).min(y_end) {
+			for p_x in ((center.x - radius - 1.0).floor() as i32).max(x_start)..((center.x + radius + 1.0).ceil() as i32).min(x_end) {
+				let distance = glm::length(&(glm::vec2(p_x as f32 + 0.5, p_y as f32 + 0.5) - center));
+				let coverage = (radius - distance + 0.5).clamp(0.0, 1.0) * opacity;
+				if coverage > 0.0 {
+					let pixel = &mut target[(p_x as usize, p_y as usize)];
+					*pixel = blend(*pixel, circle.color, coverage);
+				}
+			}
+		}
+	}
+}
+
+// a monitor's trail at its resolution, `scratch` holds the blur's output
+struct TrailLayer {
+	trails: Trails,
+	layer: Array2D<Color>,
+	scratch: Array2D<Color>
+}
+
+impl TrailLayer {
+	fn new(trails: Trails, width: usize, height: usize) -> TrailLayer {
+		TrailLayer {
+			trails,
+			layer: Array2D::new(width, height, Color::BLACK),
+			scratch: Array2D::new(if trails.blur { width } else { 0 }, if trails.blur { height } else { 0 }, Color::BLACK)
+		}
+	}
+
+	fn fade(&mut self, dt: f32) {
+		if self.trails.blur {
+			// 3x3 binomial kernel, the edges repeat
+			let (w, h) = (self.layer.width() as i32, self.layer.height() as i32);
+			for y in 0..h {
+				for x in 0..w {
+					let mut sum = [0u32; 3];
+					for d_y in -1i32..=1 {
+						for d_x in -1i32..=1 {
+							let weight = ((2 - d_x.abs()) * (2 - d_y.abs())) as u32;
+							let color = self.layer[((x + d_x).clamp(0, w - 1) as usize, (y + d_y).clamp(0, h - 1) as usize)];
+							sum[0] += color.r as u32 * weight;
+							sum[1] += color.g as u32 * weight;
+							sum[2] += color.b as u32 * weight;
+						}
+					}
+					self.scratch[(x as usize, y as usize)] = Color { r: (sum[0] / 16) as u8, g: (sum[1] / 16) as u8, b: (sum[2] / 16) as u8, a: 255 };
+				}
+			}
+			std::mem::swap(&mut self.layer, &mut self.scratch);
+		}
+
+		// rounds down, so dim trails do go away
+		let keep = self.trails.keep(dt);
+		for color in self.layer.as_mut_vec().iter_mut() {
+			color.r = (color.r as f32 * keep) as u8;
+			color.g = (color.g as f32 * keep) as u8;
+			color.b = (color.b as f32 * keep) as u8;
+		}
+	}
+}
+
+// draws into memory instead of a window, optionally saving every `frame_interval`th frame to `output`
+pub struct CpuRenderer {
+	pub frame: Array2D<Color>,
+	pub clear_color: Color,
+	placements: Vec<(i32, i32, i32, i32)>,
+	trails: Vec<Option<TrailLayer>>,
+	post: Vec<Vec<PostEffect>>,
+	output: Option<std::path::PathBuf>,
+	frame_interval: usize,
+	frame_index: usize,
+	dt: f32
+}
+
+impl CpuRenderer {
+	pub fn new(surface: &Surface, monitors: &[Monitor], effects: &[Effects], clear_color: Color, output: Option<std::path::PathBuf>, frame_interval: usize) -> Result<CpuRenderer, String> {
+		if let Some(output) = output.as_ref() {
+			std::fs::create_dir_all(output).map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+		}
+
+		// same mapping Rect does, from desktop coordinates into the surface
+		let placements = monitors.iter().map(|monitor| {
+			let x = map(surface.x as f32, (surface.x + surface.width) as f32, 0.0, surface.width as f32, monitor.x as f32) as i32;
+			let y = map(surface.y as f32, (surface.y + surface.height) as f32, 0.0, surface.height as f32, monitor.y as f32) as i32;
+			(x, y, monitor.w, monitor.h)
+		}).collect();
+
+		let trails = monitors.iter().zip(effects.iter()).map(|(monitor, effects)| {
+			effects.trails.map(|trails| TrailLayer::new(trails, monitor.w.max(1) as usize, monitor.h.max(1) as usize))
+		}).collect();
+
+		Ok(CpuRenderer {
+			frame: Array2D::new(surface.width.max(1) as usize, surface.height.max(1) as usize, clear_color),
+			clear_color,
+			placements,
+			trails,
+			post: effects.iter().map(|effects| effects.post.clone()).collect(),
+			output,
+			frame_interval: frame_interval.max(1),
+			frame_index: 0,
+			dt: 0.0
+		})
+	}
+
+	// binary PPM, rows top to bottom
+	pub fn write_ppm(&self, path: &std::path::Path) -> Result<(), String> {
+		let file = std::fs::File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+		let mut writer = std::io::BufWriter::new(file);
+		let (w, h) = (self.frame.width(), self.frame.height());
+		let mut bytes: Vec<u8> = Vec::with_capacity(w * h * 3);
+		for y in 0..h {
+			for x in 0..w {
+				let color = self.frame[(x, y)];
+				bytes.extend_from_slice(&[color.r, color.g, color.b]);
+			}
+		}
+		write!(writer, "P6\n{} {}\n255\n", w, h)
+			.and_then(|_| writer.write_all(&bytes))
+			.map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+	}
+
+	// copies monitor `index`'s trail into the frame
+	fn composite_trail(&mut self, index: usize) {
+		let (x, y, w, h) = self.placements[index];
+		let layer = &self.trails[index].as_ref().unwrap().layer;
+		for p_y in y.max(0)..(y + h).min(self.frame.height() as i32) {
+			for p_x in x.max(0)..(x + w).min(self.frame.width() as i32) {
+				self.frame[(p_x as usize, p_y as usize)] = layer[((p_x - x) as usize, (p_y - y) as usize)];
+			}
+		}
+	}
+
+	// runs monitor `index`'s post-processing over its part of the frame, only frames that
+	// get saved show it so the rest skip the work
+	fn post_process(&mut self, index: usize) {
+		let saved = self.output.is_some() && self.frame_index.is_multiple_of(self.frame_interval);
+		if self.post[index].is_empty() || !saved {
+			return;
+		}
+		let (x, y, w, h) = self.placements[index];
+		let (x, y) = (x.max(0) as usize, y.max(0) as usize);
+		let w = (w.max(0) as usize).min(self.frame.width().saturating_sub(x));
+		let h = (h.max(0) as usize).min(self.frame.height().saturating_sub(y));
+		let mut image: Array2D<Color> = Array2D::new(w, h, Color::BLACK);
+		image.copy_region(&self.frame, x, y);
+		apply(&self.post[index], &mut image);
+		for i_y in 0..h {
+			for i_x in 0..w {
+				self.frame[(x + i_x, y + i_y)] = image[(i_x, i_y)];
+			}
+		}
+	}
+}
+
+impl Renderer for CpuRenderer {
+	fn begin_frame(&mut self, dt: f32) {
+		self.dt = dt;
+		let clear_color = self.clear_color;
+		for color in self.frame.as_mut_vec().iter_mut() {
+			*color = clear_color;
+		}
+	}
+
+	fn draw(&mut self, index: usize, data: &Array2D<Color>) {
+		let (x, y, w, h) = self.placements[index];
+		let (frame_w, frame_h) = (self.frame.width() as i32, self.frame.height() as i32);
+		if w <= 0 || h <= 0 {
+			return;
+		}
+
+		// nearest neighbour, texture row 0 is the bottom of the monitor
+		let texel = |l_x: i32, l_y: i32| data[(l_x as usize * data.width() / w as usize, data.height() - 1 - (l_y as usize * data.height() / h as usize))];
+		match self.trails[index].as_mut() {
+			Some(trail) => {
+				trail.fade(self.dt);
+				for l_y in 0..h {
+					for l_x in 0..w {
+						let pixel = trail.layer[(l_x as usize, l_y as usize)];
+						trail.layer[(l_x as usize, l_y as usize)] = trail.trails.blend(pixel, texel(l_x, l_y), 1.0);
+					}
+				}
+				self.composite_trail(index);
+			},
+			None => {
+				for p_y in y.max(0)..(y + h).min(frame_h) {
+					for p_x in x.max(0)..(x + w).min(frame_w) {
+						self.frame[(p_x as usize, p_y as usize)] = texel(p_x - x, p_y - y);
+					}
+				}
+			}
+		}
+		self.post_process(index);
+	}
+
+	fn draw_circles(&mut self, index: usize, circles: &[Circle], size: glm::Vec2) {
+		let (x, y, w, h) = self.placements[index];
+		if w <= 0 || h <= 0 {
+			return;
+		}
+
+		if let Some(trail) = self.trails[index].as_mut() {
+			trail.fade(self.dt);
+			let trails = trail.trails;
+			rasterize_circles(&mut trail.layer, (0, 0, w, h), circles, size, |dst, src, amount| trails.blend(dst, src, amount));
+			self.composite_trail(index);
+			self.post_process(index);
+			return;
+		}
+
+		// the circles go over black, the same as the texture path
+		for p_y in y.max(0)..(y + h).min(self.frame.height() as i32) {
+			for p_x in x.max(0)..(x + w).min(self.frame.width() as i32) {
+				self.frame[(p_x as usize, p_y as usize)] = Color::BLACK;
+			}
+		}
+		rasterize_circles(&mut self.frame, (x, y, w, h), circles, size, blend_over);
+		self.post_process(index);
+	}
+
+	fn end_frame(&mut self) -> Result<(), String> {
+		let index = self.frame_index;
+		self.frame_index += 1;
+		match self.output.as_ref() {
+			Some(output) if index.is_multiple_of(self.frame_interval) => {
+				let path = output.join(format!("frame_{:06}.ppm", index / self.frame_interval));
+				self.write_ppm(&path)
+			},
+			_ => Ok(())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+	const GREEN: Color = Color { r: 0, g: 255, b: 0, a: 255 };
+	const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+	const GREY: Color = Color { r: 30, g: 30, b: 30, a: 255 };
+
+	fn same(a: Color, b: Color) -> bool {
+		(a.r, a.g, a.b) == (b.r, b.g, b.b)
+	}
+
+	// a monitor left of and below the primary one, so the surface starts at negative x
+	fn monitors() -> Vec<Monitor> {
+		vec![Monitor::new("left", -4, 2, 4, 3), Monitor::new("primary", 0, 0, 6, 4)]
+	}
+
+	fn renderer(monitors: &[Monitor], output: Option<std::path::PathBuf>) -> CpuRenderer {
+		let effects = vec![Effects::default(); monitors.len()];
+		CpuRenderer::new(&Surface::bounding(monitors), monitors, &effects, GREY, output, 1).unwrap()
+	}
+
+	#[test]
+	fn monitors_are_placed_in_surface_pixels() {
+		let monitors = monitors();
+		assert_eq!(Surface::bounding(&monitors), Surface { x: -4, y: 0, width: 10, height: 5 });
+		let renderer = renderer(&monitors, None);
+		assert_eq!(renderer.placements, [(0, 2, 4, 3), (4, 0, 6, 4)]);
+		assert_eq!((renderer.frame.width(), renderer.frame.height()), (10, 5));
+	}
+
+	#[test]
+	fn monitors_are_composited_into_one_frame() {
+		let monitors = monitors();
+		let mut renderer = renderer(&monitors, None);
+		// texture row 0 is the bottom of the monitor
+		let mut left = Array2D::new(2, 3, RED);
+		for x in 0..2 {
+			left[(x, 0)] = GREEN;
+		}
+		renderer.begin_frame(0.0);
+		renderer.draw(0, &left);
+		renderer.draw(1, &Array2D::new(3, 2, BLUE));
+		renderer.end_frame().unwrap();
+
+		let frame = &renderer.frame;
+		for y in 0..5 {
+			for x in 0..10 {
+				let expected = match (x, y) {
+					(0..=3, 4) => GREEN,
+					(0..=3, 2..=3) => RED,
+					(4..=9, 0..=3) => BLUE,
+					_ => GREY
+				};
+				assert!(same(frame[(x, y)], expected), "pixel ({}, {})", x, y);
+			}
+		}
+	}
+
+	#[test]
+	fn circles_cover_their_area_with_a_smooth_edge() {
+		let monitors = [Monitor::new("circles", 0, 0, 9, 9)];
+		let mut renderer = renderer(&monitors, None);
+		let white = Color { r: 255, g: 255, b: 255, a: 255 };
+		// simulation y points up, so this lands in frame row 6
+		let circles = [Circle { x: 4.5, y: 2.5, radius: 2.0, color: white }];
+		renderer.begin_frame(0.0);
+		renderer.draw_circles(0, &circles, glm::vec2(9.0, 9.0));
+		let red = |x: usize, y: usize| renderer.frame[(x, y)].r;
+		assert_eq!(red(4, 6), 255, "centre");
+		assert_eq!(red(5, 6), 255, "inside");
+		// pixel centres right on the edge are half covered
+		assert_eq!(red(6, 6), 127, "edge");
+		assert_eq!(red(4, 8), 127, "edge");
+		assert_eq!(red(7, 6), 0, "outside");
+		assert_eq!(red(4, 2), 0, "mirrored");
+		assert_eq!(red(0, 0), 0, "corner");
+
+		// half transparent circles cover half as much
+		let circles = [Circle { x: 4.5, y: 4.5, radius: 2.0, color: Color { a: 128, ..white } }];
+		renderer.begin_frame(0.0);
+		renderer.draw_circles(0, &circles, glm::vec2(9.0, 9.0));
+		assert_eq!(renderer.frame[(4, 4)].r, 128);
+	}
+
+	// red values of a 2x1 monitor with trails over frames `dt` apart
+	fn trail_frames(additive: bool, frames: &[(f32, [u8; 2])]) -> Vec<[u8; 2]> {
+		let monitors = [Monitor::new("trail", 0, 0, 2, 1)];
+		// halves every second
+		let trails = Trails { fade_rate: std::f32::consts::LN_2, additive, blur: false };
+		let effects = [Effects { trails: Some(trails), post: Vec::new() }];
+		let mut renderer = CpuRenderer::new(&Surface::bounding(&monitors), &monitors, &effects, GREY, None, 1).unwrap();
+		frames.iter().map(|(dt, reds)| {
+			let mut data = Array2D::new(2, 1, Color::BLACK);
+			for (x, red) in reds.iter().enumerate() {
+				data[(x, 0)] = Color { r: *red, g: 0, b: 0, a: 255 };
+			}
+			renderer.begin_frame(*dt);
+			renderer.draw(0, &data);
+			renderer.end_frame().unwrap();
+			[renderer.frame[(0, 0)].r, renderer.frame[(1, 0)].r]
+		}).collect()
+	}
+
+	fn close(actual: &[[u8; 2]], expected: &[[u8; 2]]) -> bool {
+		actual.len() == expected.len() && actual.iter().flatten().zip(expected.iter().flatten()).all(|(a, b)| a.abs_diff(*b) <= 1)
+	}
+
+	#[test]
+	fn trails_fade_and_keep_the_brighter_colour() {
+		let frames = trail_frames(false, &[(0.0, [200, 0]), (1.0, [0, 80]), (1.0, [60, 0]), (2.0, [0, 0])]);
+		assert!(close(&frames, &[[200, 0], [100, 80], [60, 40], [15, 10]]), "{:?}", frames);
+	}
+
+	#[test]
+	fn additive_trails_add_up_and_saturate() {
+		let frames = trail_frames(true, &[(0.0, [200, 0]), (1.0, [0, 80]), (1.0, [60, 0]), (0.0, [200, 250])]);
+		assert!(close(&frames, &[[200, 0], [100, 80], [110, 40], [255, 255]]), "{:?}", frames);
+	}
+
+	#[test]
+	fn frames_are_saved_as_ppm() {
+		let output = std::env::temp_dir().join(format!("desktop_sim_ppm_{}", std::process::id()));
+		let monitors = monitors();
+		let mut renderer = renderer(&monitors, Some(output.clone()));
+		renderer.begin_frame(0.0);
+		renderer.draw(1, &Array2D::new(1, 1, BLUE));
+		renderer.end_frame().unwrap();
+
+		let bytes = std::fs::read(output.join("frame_000000.ppm")).unwrap();
+		std::fs::remove_dir_all(&output).unwrap();
+		let header = b"P6\n10 5\n255\n";
+		assert!(bytes.starts_with(header));
+		assert_eq!(bytes.len(), header.len() + 10 * 5 * 3);
+		// rows top to bottom, the first pixel is outside both monitors
+		assert_eq!(bytes[header.len()..header.len() + 3], [30, 30, 30]);
+		let primary = header.len() + 4 * 3;
+		assert_eq!(bytes[primary..primary + 3], [0, 0, 255]);
+	}
+}
@@ -0,0 +1,213 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::config::*;
+use crate::graphics::Color;
+use crate::monitor::Monitor;
+use crate::physics::*;
+
+fn random_color(rng: &mut StdRng) -> Color {
+	Color { r: rng.gen_range(0..=255), g: rng.gen_range(0..=255), b: rng.gen_range(0..=255), a: 255 }
+}
+
+// the emitters a scene kind gets when the config doesn't list any
+fn default_emitters(kind: SceneKind) -> Vec<EmitterConfig> {
+	match kind {
+		SceneKind::Fountain => vec![EmitterConfig::default()],
+		SceneKind::Rain => (0..4).map(|i| EmitterConfig {
+			position: [(i as f32 + 0.5) / 4.0, 0.95],
+			direction: [0.0, -1.0],
+			spread: 10.0,
+			speed: [0.5, 1.0],
+			rate: 2.0,
+			color: "random".to_string(),
+			lifetime: Some(30.0),
+			..EmitterConfig::default()
+		}).collect(),
+		SceneKind::Funnel => [0.2, 0.8].iter().map(|&x| EmitterConfig {
+			position: [x, 0.95],
+			direction: [0.0, -1.0],
+			spread: 20.0,
+			speed: [0.5, 1.0],
+			rate: 3.0,
+			lifetime: Some(40.0),
+			..EmitterConfig::default()
+		}).collect(),
+		SceneKind::Pegboard => vec![EmitterConfig {
+			position: [0.5, 0.9],
+			direction: [0.0, -1.0],
+			spread: 20.0,
+			speed: [0.5, 2.0],
+			rate: 4.0,
+			lifetime: Some(60.0),
+			..EmitterConfig::default()
+		}],
+		SceneKind::Hourglass => vec![EmitterConfig {
+			position: [0.5, 0.9],
+			direction: [0.0, -1.0],
+			spread: 30.0,
+			rate: 20.0,
+			max_count: Some(400),
+			..EmitterConfig::default()
+		}],
+		SceneKind::Cloth | SceneKind::Empty => Vec::new()
+	}
+}
+
+// wall between two points, thick enough that fast particles don't slip through
+fn wall(a: [f32; 2], b: [f32; 2]) -> ColliderConfig {
+	ColliderConfig::Capsule { a, b, radius: 1.0 }
+}
+
+// the colliders a scene kind gets when the config doesn't list any
+fn default_colliders(kind: SceneKind) -> Vec<ColliderConfig> {
+	match kind {
+		SceneKind::Funnel => vec![wall([0.05, 0.8], [0.46, 0.4]), wall([0.95, 0.8], [0.54, 0.4])],
+		SceneKind::Pegboard => {
+			// staggered rows, so whatever bounces sideways runs into the next peg
+			let (left, right, spacing) = (0.25, 0.75, 0.03);
+			let mut colliders: Vec<ColliderConfig> = Vec::new();
+			for row in 0..8 {
+				let mut x = left + (row % 2) as f32 * spacing * 0.5;
+				while x <= right {
+					colliders.push(ColliderConfig::Circle { center: [x, 0.8 - row as f32 * 0.06], radius: 1.0 });
+					x += spacing;
+				}
+			}
+			// bins under the pegs
+			let mut x = left;
+			while x <= right {
+				colliders.push(wall([x, 0.0], [x, 0.25]));
+				x += spacing * 2.0;
+			}
+			colliders
+		},
+		SceneKind::Hourglass => vec![
+			wall([0.3, 1.0], [0.48, 0.5]),
+			wall([0.7, 1.0], [0.52, 0.5]),
+			wall([0.48, 0.5], [0.3, 0.0]),
+			wall([0.52, 0.5], [0.7, 0.0])
+		],
+		SceneKind::Fountain | SceneKind::Rain | SceneKind::Cloth | SceneKind::Empty => Vec::new()
+	}
+}
+
+fn emitter(config: &EmitterConfig, palette: &[Color], size: glm::Vec2, rng: &mut StdRng) -> Emitter {
+	let position = glm::vec2(config.position[0] * (size.x - 1.0), config.position[1] * (size.y - 1.0));
+	let mut emitter = Emitter::new(position, glm::vec2(config.direction[0], config.direction[1]), rng.gen());
+	emitter.spread = config.spread.to_radians();
+	emitter.speed = (config.speed[0], config.speed[1]);
+	emitter.mode = match config.burst {
+		Some(burst) => EmitMode::Burst { count: burst.count, interval: burst.interval },
+		None => EmitMode::Continuous { rate: config.rate }
+	};
+	emitter.color = match config.color.as_str() {
+		"cycle" => ColorGenerator::Cycle { color: random_color(rng), change: [1, -1, 1] },
+		"random" => ColorGenerator::Random,
+		"palette" => ColorGenerator::Palette { colors: palette.to_vec(), index: 0 },
+		color => ColorGenerator::Fixed(parse_color(color).unwrap())
+	};
+	emitter.radius = config.radius;
+	emitter.mass = config.mass;
+	emitter.lifetime = config.lifetime;
+	emitter.max_count = config.max_count;
+	emitter
+}
+
+fn collider(config: &ColliderConfig, size: glm::Vec2) -> Collider {
+	let point = |point: &[f32; 2]| glm::vec2(point[0] * size.x, point[1] * size.y);
+	match config {
+		ColliderConfig::Segment { a, b } => Collider::Segment { a: point(a), b: point(b) },
+		ColliderConfig::Circle { center, radius } => Collider::Circle { center: point(center), radius: *radius },
+		ColliderConfig::Capsule { a, b, radius } => Collider::Capsule { a: point(a), b: point(b), radius: *radius },
+		ColliderConfig::Polygon { points } => Collider::polygon(points.iter().map(point).collect()).unwrap()
+	}
+}
+
+// simulation cells across and down the monitor
+pub fn size(scene: &SceneConfig, monitor: &Monitor) -> (usize, usize) {
+	((monitor.w.max(0) as usize / scene.downscale).max(1), (monitor.h.max(0) as usize / scene.downscale).max(1))
+}
+
+// builds the simulation for one monitor, the config has to be validated already, the same seed builds the same scene
+pub fn build(scene: &SceneConfig, monitor: &Monitor, seed: u64) -> Physics {
+	let mut rng = StdRng::seed_from_u64(seed);
+	let (width, height) = size(scene, monitor);
+	let mut simulation = Physics::new(width, height);
+	scene.physics.apply(&mut simulation.config);
+
+	let palette: Vec<Color> = scene.palette.iter().map(|color| parse_color(color).unwrap()).collect();
+	let size = simulation.size;
+	let point = |point: [f32; 2]| glm::vec2(point[0] * size.x, point[1] * size.y);
+	let mut color = |color: &Option<String>| color.as_deref().and_then(parse_color).or_else(|| palette.first().copied()).unwrap_or_else(|| random_color(&mut rng));
+	if scene.kind == SceneKind::Cloth {
+		let spacing = 1.5;
+		let cloth = ClothConfig {
+			columns: (size.x * 0.6 / spacing) as usize,
+			rows: (size.y * 0.4 / spacing) as usize,
+			spacing,
+			..ClothConfig::default()
+		};
+		simulation.add_cloth(glm::vec2(size.x * 0.2, size.y - 1.0), &cloth, color(&None));
+	}
+	for rope in scene.ropes.iter() {
+		let chain = simulation.add_rope(point(rope.start), point(rope.end), rope.segments, rope.stiffness, rope.pinned, color(&rope.color));
+		if rope.bend_stiffness > 0.0 {
+			simulation.add_bending(&chain, rope.bend_stiffness);
+		}
+	}
+	for soft_body in scene.soft_bodies.iter() {
+		simulation.add_soft_body(point(soft_body.center), soft_body.radius, soft_body.count, soft_body.pressure, soft_body.stiffness, color(&soft_body.color));
+	}
+
+	let colliders = scene.colliders.clone().unwrap_or_else(|| default_colliders(scene.kind));
+	for config in colliders.iter() {
+		simulation.add_collider(collider(config, size));
+	}
+
+	for drain in scene.drains.iter() {
+		simulation.add_drain(point(drain.min), point(drain.max));
+	}
+
+	for config in scene.force_fields.iter() {
+		simulation.add_force_field(config.force_field(size, rng.gen()));
+	}
+
+	let emitters = scene.emitters.clone().unwrap_or_else(|| default_emitters(scene.kind));
+	for config in emitters.iter() {
+		simulation.add_emitter(emitter(config, &palette, size, &mut rng));
+	}
+	simulation
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn snapshot(seed: u64) -> Vec<(u32, u32, [u8; 3])> {
+		let scene = SceneConfig {
+			emitters: Some(vec![
+				EmitterConfig { color: "random".to_owned(), ..EmitterConfig::default() },
+				EmitterConfig { position: [0.2, 0.8], color: "cycle".to_owned(), ..EmitterConfig::default() }
+			]),
+			force_fields: vec![ForceFieldSection::Turbulence(TurbulenceSection::default())],
+			ropes: vec![RopeConfig::default()],
+			..SceneConfig::default()
+		};
+		let mut simulation = build(&scene, &Monitor::new("test", 0, 0, 1920, 1080), seed);
+		for _ in 0..60 {
+			simulation.update(1.0 / 30.0);
+		}
+		let mut circles = Vec::new();
+		simulation.circles(&mut circles, 1.0);
+		circles.iter().map(|circle| (circle.x.to_bits(), circle.y.to_bits(), [circle.color.r, circle.color.g, circle.color.b])).collect()
+	}
+
+	#[test]
+	fn the_same_seed_builds_the_same_scene() {
+		let scene = snapshot(42);
+		assert!(scene.len() > 21, "nothing was emitted");
+		assert!(snapshot(42) == scene, "the same seed built a different scene");
+		assert!(snapshot(43) != scene, "a different seed built the same scene");
+	}
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+// runs physics in fixed steps however fast frames are drawn, the caller measures
+// the time between frames and passes it in
+pub struct FixedTimestep {
+	// simulated seconds per update
+	pub timestep: f32,
+	// most updates run for one frame, a longer backlog is dropped so slow frames don't snowball
+	pub max_updates: usize,
+	pub fps_cap: Option<f32>,
+	accumulator: f32,
+	frame_time: f32
+}
+
+impl FixedTimestep {
+	pub fn new(timestep: f32, max_updates: usize, fps_cap: Option<f32>) -> FixedTimestep {
+		FixedTimestep {
+			timestep,
+			max_updates: max_updates.max(1),
+			fps_cap: fps_cap.filter(|fps_cap| *fps_cap > 0.0),
+			accumulator: 0.0,
+			frame_time: 0.0
+		}
+	}
+
+	// starts a frame `elapsed` after the previous one and returns how many updates are due
+	pub fn begin_frame(&mut self, elapsed: Duration) -> usize {
+		self.frame_time = elapsed.as_secs_f32();
+		self.accumulator = (self.accumulator + self.frame_time).min(self.timestep * self.max_updates as f32);
+		let updates = (self.accumulator / self.timestep) as usize;
+		self.accumulator -= updates as f32 * self.timestep;
+		updates
+	}
+
+	// forgets the leftover time, so resuming after a pause doesn't catch up
+	pub fn reset(&mut self) {
+		self.accumulator = 0.0;
+	}
+
+	// how far the frame lies between the last two updates, from 0 to 1
+	pub fn alpha(&self) -> f32 {
+		(self.accumulator / self.timestep).clamp(0.0, 1.0)
+	}
+
+	// wall clock seconds passed to the last begin_frame
+	pub fn frame_time(&self) -> f32 {
+		self.frame_time
+	}
+
+	// how long to sleep off a frame that took `elapsed` so far when the frame rate is capped
+	pub fn sleep_time(&self, elapsed: Duration) -> Duration {
+		match self.fps_cap {
+			Some(fps_cap) => Duration::from_secs_f32(1.0 / fps_cap).saturating_sub(elapsed),
+			None => Duration::ZERO
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn seconds(seconds: f32) -> Duration {
+		Duration::from_secs_f32(seconds)
+	}
+
+	#[test]
+	fn leftover_time_carries_into_the_next_frame() {
+		let mut timestep = FixedTimestep::new(0.25, 8, None);
+		let frames = [(0.625, 2, 0.5), (0.0625, 0, 0.75), (0.125, 1, 0.25), (0.0, 0, 0.25)];
+		for (elapsed, updates, alpha) in frames {
+			assert_eq!(timestep.begin_frame(seconds(elapsed)), updates, "after {}s", elapsed);
+			assert!((timestep.alpha() - alpha).abs() < 1e-4, "alpha {} after {}s", timestep.alpha(), elapsed);
+			assert!((timestep.frame_time() - elapsed).abs() < 1e-6);
+		}
+
+		timestep.reset();
+		assert!(timestep.alpha() == 0.0);
+		assert_eq!(timestep.begin_frame(seconds(0.125)), 0);
+	}
+
+	#[test]
+	fn long_frames_drop_the_backlog() {
+		let mut timestep = FixedTimestep::new(0.25, 4, None);
+		assert_eq!(timestep.begin_frame(seconds(10.0)), 4);
+		assert!(timestep.alpha() == 0.0);
+		// nothing left over to catch up on
+		assert_eq!(timestep.begin_frame(seconds(0.125)), 0);
+		assert_eq!(timestep.begin_frame(seconds(0.125)), 1);
+	}
+
+	#[test]
+	fn capped_frames_sleep_off_the_rest() {
+		let timestep = FixedTimestep::new(0.25, 4, Some(10.0));
+		let rest = timestep.sleep_time(Duration::from_millis(30));
+		assert!((rest.as_secs_f32() - 0.07).abs() < 1e-4, "sleeps {:?}", rest);
+		assert_eq!(timestep.sleep_time(Duration::from_millis(150)), Duration::ZERO);
+
+		assert_eq!(FixedTimestep::new(0.25, 4, None).sleep_time(Duration::ZERO), Duration::ZERO);
+		assert!(FixedTimestep::new(0.25, 4, Some(0.0)).fps_cap.is_none());
+	}
+}
@@ -0,0 +1,233 @@
+use crate::monitor::Monitor;
+use crate::physics::*;
+
+// maps between a simulation and desktop pixels, the simulation's y axis points up
+#[derive(Clone, Copy)]
+struct Frame {
+	x: f32,
+	bottom: f32,
+	// desktop pixels per simulation unit
+	scale: glm::Vec2
+}
+
+impl Frame {
+	fn new(monitor: &Monitor, size: glm::Vec2) -> Frame {
+		Frame {
+			x: monitor.x as f32,
+			bottom: (monitor.y + monitor.h) as f32,
+			scale: glm::vec2(monitor.w as f32 / size.x, monitor.h as f32 / size.y)
+		}
+	}
+
+	fn desktop_point(&self, pos: glm::Vec2) -> glm::Vec2 {
+		glm::vec2(self.x + pos.x * self.scale.x, self.bottom - pos.y * self.scale.y)
+	}
+
+	fn simulation_point(&self, pos: glm::Vec2) -> glm::Vec2 {
+		glm::vec2((pos.x - self.x) / self.scale.x, (self.bottom - pos.y) / self.scale.y)
+	}
+
+	fn desktop_vector(&self, v: glm::Vec2) -> glm::Vec2 {
+		glm::vec2(v.x * self.scale.x, -v.y * self.scale.y)
+	}
+
+	fn simulation_vector(&self, v: glm::Vec2) -> glm::Vec2 {
+		glm::vec2(v.x / self.scale.x, -v.y / self.scale.y)
+	}
+}
+
+// part of an edge of monitor `from` that monitor `to` sits right against
+struct Link {
+	from: usize,
+	to: usize,
+	edge: Edge,
+	// desktop pixels, along y for left and right edges, along x for top and bottom
+	start: i32,
+	end: i32
+}
+
+// how the simulated monitors touch on the virtual desktop, objects cross shared edges
+// and bounce off the rest, including gaps and the parts of taller monitors that stick out
+pub struct Topology {
+	links: Vec<Link>
+}
+
+impl Topology {
+	pub fn new(monitors: &[Monitor]) -> Topology {
+		let mut links: Vec<Link> = Vec::new();
+		for (from, a) in monitors.iter().enumerate() {
+			for (to, b) in monitors.iter().enumerate().filter(|(to, _)| *to != from) {
+				let (y_start, y_end) = (a.y.max(b.y), (a.y + a.h).min(b.y + b.h));
+				let (x_start, x_end) = (a.x.max(b.x), (a.x + a.w).min(b.x + b.w));
+				let edge = if a.x + a.w == b.x && y_start < y_end {
+					Some((Edge::Right, y_start, y_end))
+				} else if b.x + b.w == a.x && y_start < y_end {
+					Some((Edge::Left, y_start, y_end))
+				} else if a.y + a.h == b.y && x_start < x_end {
+					// desktop y points down, so below on the desktop is the bottom of the simulation
+					Some((Edge::Bottom, x_start, x_end))
+				} else if b.y + b.h == a.y && x_start < x_end {
+					Some((Edge::Top, x_start, x_end))
+				} else {
+					None
+				};
+				if let Some((edge, start, end)) = edge {
+					links.push(Link { from, to, edge, start, end });
+				}
+			}
+		}
+		Topology { links }
+	}
+
+	// opens the shared edges of every simulation, `simulations` pairs up with `monitors`
+	pub fn open_edges(&self, monitors: &[Monitor], simulations: &mut [Physics]) {
+		for (index, (monitor, simulation)) in monitors.iter().zip(simulations.iter_mut()).enumerate() {
+			let frame = Frame::new(monitor, simulation.size);
+			let openings = self.links.iter().filter(|link| link.from == index).map(|link| {
+				let (start, end) = match link.edge {
+					Edge::Left | Edge::Right => {
+						let start = frame.simulation_point(glm::vec2(0.0, link.end as f32)).y;
+						let end = frame.simulation_point(glm::vec2(0.0, link.start as f32)).y;
+						(start, end)
+					},
+					Edge::Bottom | Edge::Top => (frame.simulation_point(glm::vec2(link.start as f32, 0.0)).x, frame.simulation_point(glm::vec2(link.end as f32, 0.0)).x)
+				};
+				Opening { edge: link.edge, start, end }
+			}).collect();
+			simulation.set_openings(openings);
+		}
+	}
+
+	// moves the objects that left a simulation through a shared edge over to the neighbour
+	pub fn transfer(&self, monitors: &[Monitor], simulations: &mut [Physics]) {
+		if self.links.is_empty() {
+			return;
+		}
+		let frames: Vec<Frame> = monitors.iter().zip(simulations.iter()).map(|(monitor, simulation)| Frame::new(monitor, simulation.size)).collect();
+		for from in 0..simulations.len().min(frames.len()) {
+			for object in simulations[from].take_departed() {
+				let pos = frames[from].desktop_point(object.pos);
+				let to = self.links.iter()
+					.filter(|link| link.from == from)
+					.map(|link| link.to)
+					.find(|&to| {
+						let monitor = &monitors[to];
+						pos.x >= monitor.x as f32 && pos.x <= (monitor.x + monitor.w) as f32 && pos.y >= monitor.y as f32 && pos.y <= (monitor.y + monitor.h) as f32
+					});
+
+				match to {
+					Some(to) => {
+						let (source, target) = (frames[from], frames[to]);
+						let radius_scale = (source.scale.x / target.scale.x + source.scale.y / target.scale.y) * 0.5;
+						let mut moved = object;
+						moved.pos = target.simulation_point(pos);
+						moved.last_pos = target.simulation_point(source.desktop_point(object.last_pos));
+						moved.vel = target.simulation_vector(source.desktop_vector(object.vel));
+						moved.acc = target.simulation_vector(source.desktop_vector(object.acc));
+						moved.radius = object.radius * radius_scale;
+						simulations[to].insert_object(moved);
+					},
+					// slipped out past the end of the shared stretch, put it back against the wall
+					None => {
+						let size = simulations[from].size;
+						let mut returned = object;
+						returned.pos = glm::clamp_vec(&object.pos, &glm::vec2(object.radius, object.radius), &(size - glm::vec2(object.radius, object.radius)));
+						returned.last_pos = returned.pos;
+						simulations[from].insert_object(returned);
+					}
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const WHITE: crate::graphics::Color = crate::graphics::Color { r: 255, g: 255, b: 255, a: 255 };
+
+	fn stretches(topology: &Topology) -> Vec<(usize, usize, Edge, i32, i32)> {
+		topology.links.iter().map(|link| (link.from, link.to, link.edge, link.start, link.end)).collect()
+	}
+
+	// an object at `pos` in simulation units moving at `vel`
+	fn add_moving(simulation: &mut Physics, pos: glm::Vec2, vel: glm::Vec2) {
+		let handle = simulation.add_object(pos.x, pos.y, WHITE).unwrap();
+		simulation[handle].pos = pos;
+		simulation[handle].last_pos = pos;
+		simulation[handle].vel = vel;
+	}
+
+	#[test]
+	fn side_by_side_monitors_share_an_edge() {
+		let monitors = [Monitor::new("left", 0, 0, 1920, 1080), Monitor::new("right", 1920, 0, 1920, 1080)];
+		assert!(stretches(&Topology::new(&monitors)) == [(0, 1, Edge::Right, 0, 1080), (1, 0, Edge::Left, 0, 1080)]);
+
+		let stacked = [Monitor::new("top", 0, 0, 1920, 1080), Monitor::new("bottom", 960, 1080, 1920, 1080)];
+		assert!(stretches(&Topology::new(&stacked)) == [(0, 1, Edge::Bottom, 960, 1920), (1, 0, Edge::Top, 960, 1920)]);
+	}
+
+	#[test]
+	fn monitors_with_a_gap_are_not_linked() {
+		let monitors = [Monitor::new("left", 0, 0, 1920, 1080), Monitor::new("right", 2000, 0, 1920, 1080)];
+		assert!(Topology::new(&monitors).links.is_empty());
+		// touching at a corner only
+		let diagonal = [Monitor::new("left", 0, 0, 1920, 1080), Monitor::new("right", 1920, 1080, 1920, 1080)];
+		assert!(Topology::new(&diagonal).links.is_empty());
+	}
+
+	#[test]
+	fn only_the_overlap_of_different_heights_is_open() {
+		let monitors = [Monitor::new("left", 0, 0, 1920, 1080), Monitor::new("right", 1920, 200, 1280, 720)];
+		let topology = Topology::new(&monitors);
+		assert!(stretches(&topology) == [(0, 1, Edge::Right, 200, 920), (1, 0, Edge::Left, 200, 920)]);
+
+		// 16 desktop pixels per unit, the open stretch is y 10 to 55 of the left simulation
+		let mut simulations = vec![Physics::new(120, 68), Physics::new(80, 45)];
+		topology.open_edges(&monitors, &mut simulations);
+		add_moving(&mut simulations[0], glm::vec2(119.0, 30.0), glm::vec2(30.0, 0.0));
+		add_moving(&mut simulations[0], glm::vec2(119.0, 62.0), glm::vec2(30.0, 0.0));
+		for _ in 0..10 {
+			for simulation in simulations.iter_mut() {
+				simulation.update(1.0 / 30.0);
+			}
+			topology.transfer(&monitors, &mut simulations);
+		}
+		assert_eq!(simulations[1].object_count(), 1, "the object in the stretch didn't cross");
+		assert_eq!(simulations[0].object_count(), 1, "the object above the stretch got through");
+	}
+
+	#[test]
+	fn transfer_keeps_desktop_position_and_velocity() {
+		let monitors = [Monitor::new("left", 0, 0, 1920, 1088), Monitor::new("right", 1920, 0, 1920, 1088)];
+		let topology = Topology::new(&monitors);
+		// 16 and 8 desktop pixels per unit
+		let mut simulations = vec![Physics::new(120, 68), Physics::new(240, 136)];
+		topology.open_edges(&monitors, &mut simulations);
+		add_moving(&mut simulations[0], glm::vec2(120.5, 34.0), glm::vec2(30.0, 5.0));
+		topology.transfer(&monitors, &mut simulations);
+
+		assert_eq!(simulations[0].object_count(), 0);
+		assert_eq!(simulations[1].object_count(), 1);
+		let object = simulations[1][0];
+		// desktop (1928, 544) moving at (480, -80) pixels per second
+		assert!(glm::distance(&object.pos, &glm::vec2(1.0, 68.0)) < 1e-3, "landed at {:?}", object.pos);
+		assert!(glm::distance(&object.vel, &glm::vec2(60.0, 10.0)) < 1e-3, "moving at {:?}", object.vel);
+		assert!((object.radius - DEFAULT_RADIUS * 2.0).abs() < 1e-3);
+	}
+
+	#[test]
+	fn transfers_ignore_the_population_limit() {
+		let monitors = [Monitor::new("left", 0, 0, 1920, 1080), Monitor::new("right", 1920, 0, 1920, 1080)];
+		let topology = Topology::new(&monitors);
+		let mut simulations = vec![Physics::new(120, 68), Physics::new(120, 68)];
+		simulations[1].config.max_objects = Some(1);
+		simulations[1].config.population_policy = PopulationPolicy::RefuseSpawn;
+		simulations[1].add_object(60.0, 30.0, WHITE).unwrap();
+		topology.open_edges(&monitors, &mut simulations);
+		add_moving(&mut simulations[0], glm::vec2(120.5, 34.0), glm::vec2(30.0, 0.0));
+		topology.transfer(&monitors, &mut simulations);
+		assert_eq!(simulations[0].object_count() + simulations[1].object_count(), 2, "a crossing object was lost");
+	}
+}
@@ -0,0 +1,193 @@
+use crate::array2d::*;
+use crate::config::SceneConfig;
+use crate::graphics::{Circle, Color};
+use crate::monitor::Monitor;
+use crate::physics::*;
+use crate::render::{DrawMode, MonitorFrame, Surface};
+use crate::scene;
+
+// one simulation over the bounding box of all monitors, the space between them is solid,
+// it renders into one frame that gets cut into a texture or a set of circles per monitor
+pub struct Unified {
+	// desktop pixels the simulation covers
+	bounds: Surface,
+	draw: DrawMode,
+	frame: Array2D<Color>,
+	circles: Vec<Circle>
+}
+
+// the bounding box as one big monitor, so scenes build the same way they do per monitor
+fn bounding_monitor(bounds: &Surface) -> Monitor {
+	Monitor::new("desktop", bounds.x, bounds.y, bounds.width.max(1), bounds.height.max(1))
+}
+
+pub fn build(scene: &SceneConfig, monitors: &[Monitor], seed: u64) -> (Physics, Unified) {
+	let bounds = Surface::bounding(monitors);
+	let mut simulation = scene::build(scene, &bounding_monitor(&bounds), seed);
+	add_gap_colliders(&mut simulation, &bounds, monitors);
+	let unified = Unified {
+		bounds,
+		draw: scene.draw_mode(),
+		frame: Array2D::new(simulation.size.x as usize, simulation.size.y as usize, Color::BLACK),
+		circles: Vec::new()
+	};
+	(simulation, unified)
+}
+
+// fills every part of the bounding box no monitor covers with rectangle colliders
+fn add_gap_colliders(simulation: &mut Physics, bounds: &Surface, monitors: &[Monitor]) {
+	let mut xs: Vec<i32> = vec![bounds.x, bounds.x + bounds.width];
+	let mut ys: Vec<i32> = vec![bounds.y, bounds.y + bounds.height];
+	for monitor in monitors.iter() {
+		xs.extend([monitor.x, monitor.x + monitor.w]);
+		ys.extend([monitor.y, monitor.y + monitor.h]);
+	}
+	xs.sort();
+	xs.dedup();
+	ys.sort();
+	ys.dedup();
+
+	let covered = |x: f32, y: f32| monitors.iter().any(|monitor| x > monitor.x as f32 && x < (monitor.x + monitor.w) as f32 && y > monitor.y as f32 && y < (monitor.y + monitor.h) as f32);
+	let scale = glm::vec2(simulation.size.x / bounds.width.max(1) as f32, simulation.size.y / bounds.height.max(1) as f32);
+	let to_simulation = |x: i32, y: i32| glm::vec2((x - bounds.x) as f32 * scale.x, (bounds.y + bounds.height - y) as f32 * scale.y);
+
+	// the grid lines through every monitor edge split the box into cells that are either
+	// covered or not, uncovered cells next to each other in a row become one rectangle
+	for row in ys.windows(2) {
+		let center_y = (row[0] + row[1]) as f32 * 0.5;
+		let mut start: Option<i32> = None;
+		for column in xs.windows(2) {
+			let gap = !covered((column[0] + column[1]) as f32 * 0.5, center_y);
+			match (gap, start) {
+				(true, None) => start = Some(column[0]),
+				(false, Some(x)) => {
+					simulation.add_collider(Collider::rectangle(to_simulation(x, row[1]), to_simulation(column[0], row[0])));
+					start = None;
+				},
+				_ => {}
+			}
+		}
+		if let Some(x) = start {
+			simulation.add_collider(Collider::rectangle(to_simulation(x, row[1]), to_simulation(xs[xs.len() - 1], row[0])));
+		}
+	}
+}
+
+impl Unified {
+	// part of the frame showing `monitor`, in cells with row 0 at the bottom
+	fn slice(&self, monitor: &Monitor) -> (usize, usize, usize, usize) {
+		let (frame_w, frame_h) = (self.frame.width() as f32, self.frame.height() as f32);
+		let scale_x = frame_w / self.bounds.width.max(1) as f32;
+		let scale_y = frame_h / self.bounds.height.max(1) as f32;
+		let x0 = ((monitor.x - self.bounds.x) as f32 * scale_x).round().clamp(0.0, frame_w - 1.0);
+		let x1 = ((monitor.x + monitor.w - self.bounds.x) as f32 * scale_x).round().clamp(x0 + 1.0, frame_w);
+		let y0 = ((self.bounds.y + self.bounds.height - monitor.y - monitor.h) as f32 * scale_y).round().clamp(0.0, frame_h - 1.0);
+		let y1 = ((self.bounds.y + self.bounds.height - monitor.y) as f32 * scale_y).round().clamp(y0 + 1.0, frame_h);
+		(x0 as usize, y0 as usize, (x1 - x0) as usize, (y1 - y0) as usize)
+	}
+
+	// one frame per monitor, sized to its slice of the simulation
+	pub fn frames(&self, monitors: &[Monitor]) -> Vec<MonitorFrame> {
+		monitors.iter().map(|monitor| {
+			let (_, _, w, h) = self.slice(monitor);
+			MonitorFrame::new(self.draw, w, h)
+		}).collect()
+	}
+
+	// follows the monitors after one was added, removed or resized, keeping the particles
+	pub fn relayout(&mut self, simulation: &mut Physics, scene: &SceneConfig, monitors: &[Monitor], mode: ResizeMode) {
+		self.bounds = Surface::bounding(monitors);
+		let (w, h) = scene::size(scene, &bounding_monitor(&self.bounds));
+		simulation.resize(w, h, mode);
+		simulation.clear_colliders();
+		add_gap_colliders(simulation, &self.bounds, monitors);
+		self.frame = Array2D::new(w, h, Color::BLACK);
+	}
+
+	pub fn render(&mut self, simulation: &Physics, alpha: f32, monitors: &[Monitor], frames: &mut [MonitorFrame]) {
+		match self.draw {
+			DrawMode::Texture => {
+				for color in self.frame.as_mut_vec().iter_mut() {
+					*color = Color::BLACK;
+				}
+				simulation.render_interpolated(&mut self.frame, alpha);
+			},
+			DrawMode::Circles => simulation.circles(&mut self.circles, alpha)
+		}
+		for (monitor, frame) in monitors.iter().zip(frames.iter_mut()) {
+			let (x, y, w, h) = self.slice(monitor);
+			match frame {
+				MonitorFrame::Texture(texture_data) => texture_data.copy_region(&self.frame, x, y),
+				// the circles touching the slice, moved to its corner
+				MonitorFrame::Circles { circles, size } => {
+					let (x, y, w, h) = (x as f32, y as f32, w as f32, h as f32);
+					circles.clear();
+					circles.extend(self.circles.iter()
+						.filter(|circle| circle.x + circle.radius > x && circle.x - circle.radius < x + w && circle.y + circle.radius > y && circle.y - circle.radius < y + h)
+						.map(|circle| Circle { x: circle.x - x, y: circle.y - y, ..*circle }));
+					*size = glm::vec2(w, h);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::SceneKind;
+
+	const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+
+	// 16 desktop pixels per cell, with a monitor of 1600x960 on the left
+	fn unified(right: Monitor) -> (Physics, Unified, Vec<Monitor>) {
+		let scene = SceneConfig { kind: SceneKind::Empty, downscale: 16, ..SceneConfig::default() };
+		let monitors = vec![Monitor::new("left", 0, 0, 1600, 960), right];
+		let (simulation, unified) = build(&scene, &monitors, 0);
+		(simulation, unified, monitors)
+	}
+
+	fn gaps(simulation: &Physics) -> Vec<[f32; 4]> {
+		simulation.colliders().iter().map(|collider| {
+			let (min, max) = collider.bounds();
+			[min.x, min.y, max.x, max.y]
+		}).collect()
+	}
+
+	#[test]
+	fn an_l_shaped_layout_walls_off_the_corner() {
+		let (simulation, _, _) = unified(Monitor::new("right", 1600, 480, 800, 480));
+		assert!(simulation.size == glm::vec2(150.0, 60.0));
+		// the top right of the desktop is the top right of the simulation
+		assert_eq!(gaps(&simulation), [[100.0, 30.0, 150.0, 60.0]]);
+	}
+
+	#[test]
+	fn an_offset_monitor_gets_walls_above_and_below() {
+		let (simulation, _, _) = unified(Monitor::new("right", 1600, 320, 800, 320));
+		assert_eq!(gaps(&simulation), [[100.0, 40.0, 150.0, 60.0], [100.0, 0.0, 150.0, 20.0]]);
+
+		let (simulation, _, _) = unified(Monitor::new("right", 1600, 0, 800, 960));
+		assert!(gaps(&simulation).is_empty());
+	}
+
+	#[test]
+	fn slices_map_monitors_to_their_part_of_the_frame() {
+		let (mut simulation, mut unified, monitors) = unified(Monitor::new("right", 1600, 320, 800, 320));
+		// row 0 is the bottom, so the slice of a monitor lower on the desktop starts lower
+		assert_eq!(unified.slice(&monitors[0]), (0, 0, 100, 60));
+		assert_eq!(unified.slice(&monitors[1]), (100, 20, 50, 20));
+
+		simulation.add_object_with(110.5, 35.5, DEFAULT_RADIUS, 1.0, WHITE).unwrap();
+		let mut frames = unified.frames(&monitors);
+		unified.render(&simulation, 1.0, &monitors, &mut frames);
+		match &frames[1] {
+			MonitorFrame::Texture(texture) => {
+				assert_eq!((texture.width(), texture.height()), (50, 20));
+				let lit: Vec<(usize, usize)> = (0..20).flat_map(|y| (0..50).map(move |x| (x, y))).filter(|&(x, y)| texture[(x, y)].r == 255).collect();
+				assert_eq!(lit, [(10, 15)]);
+			},
+			_ => panic!("expected a texture")
+		}
+	}
+}
@@ -0,0 +1,544 @@
+extern crate khronos_egl as egl;
+extern crate wayland_client;
+extern crate wayland_protocols_wlr;
+extern crate wayland_sys;
+
+use wayland_client::protocol::{wl_compositor, wl_output, wl_registry, wl_surface};
+use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{zwlr_foreign_toplevel_handle_v1, zwlr_foreign_toplevel_manager_v1};
+use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+use wayland_sys::egl::*;
+use wayland_sys::ffi_dispatch;
+
+use crate::monitor::*;
+use crate::platform::*;
+use crate::render::Surface;
+
+struct Output {
+	output: wl_output::WlOutput,
+	// registry name of the wl_output global, outputs are looked up by it
+	global: u32,
+	name: String,
+	x: i32,
+	y: i32,
+	w: i32,
+	h: i32,
+	scale: i32,
+	// mHz
+	refresh: i32,
+	// the compositor finished sending the output's geometry and mode
+	done: bool,
+	layer: Option<Layer>
+}
+
+// a background layer surface covering one output
+struct Layer {
+	surface: wl_surface::WlSurface,
+	layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+	width: i32,
+	height: i32,
+	configured: bool,
+	egl_window: *mut wl_egl_window,
+	egl_surface: Option<egl::Surface>,
+	// creating the EGL surface failed, the output stays blank instead of retrying every frame
+	failed: bool
+}
+
+// another client's window, tracked to notice fullscreen apps
+struct Toplevel {
+	handle: zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1,
+	activated: bool,
+	fullscreen: bool
+}
+
+#[derive(Default)]
+struct State {
+	compositor: Option<wl_compositor::WlCompositor>,
+	layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+	// missing on compositors without wlr-foreign-toplevel-management
+	toplevel_manager: Option<zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1>,
+	toplevels: Vec<Toplevel>,
+	outputs: Vec<Output>,
+	// layers of unplugged or disabled outputs, waiting for their EGL surfaces to be destroyed
+	dead_layers: Vec<Layer>,
+	closed: bool
+}
+
+pub struct WaylandPlatform {
+	connection: Connection,
+	queue: EventQueue<State>,
+	state: State,
+	egl: egl::DynamicInstance<egl::EGL1_4>,
+	display: Option<egl::Display>,
+	config: Option<egl::Config>,
+	context: Option<egl::Context>,
+	surface: Surface,
+	watcher: MonitorWatcher
+}
+
+impl WaylandPlatform {
+	pub fn new() -> Result<WaylandPlatform, String> {
+		let connection = Connection::connect_to_env().map_err(|e| format!("Failed to connect to the Wayland compositor: {}", e))?;
+		let mut queue = connection.new_event_queue::<State>();
+		connection.display().get_registry(&queue.handle(), ());
+
+		let mut state = State::default();
+		// once for the globals, once more for the output geometry and modes
+		for _ in 0..2 {
+			queue.roundtrip(&mut state).map_err(|e| format!("Failed to talk to the Wayland compositor: {}", e))?;
+		}
+		if state.compositor.is_none() {
+			return Err("Wayland compositor has no wl_compositor".to_string());
+		}
+		if state.layer_shell.is_none() {
+			return Err("Wayland compositor does not support zwlr_layer_shell_v1".to_string());
+		}
+		// version 1 outputs never send done, everything has arrived after the roundtrips anyway
+		for output in state.outputs.iter_mut() {
+			output.done = true;
+		}
+
+		let egl = unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() }.map_err(|e| format!("Failed to load EGL: {}", e))?;
+
+		Ok(WaylandPlatform {
+			connection,
+			queue,
+			state,
+			egl,
+			display: None,
+			config: None,
+			context: None,
+			surface: Surface { x: 0, y: 0, width: 0, height: 0 },
+			watcher: MonitorWatcher::new(Vec::new())
+		})
+	}
+
+	// asks for a background layer surface on the output, usable once the compositor configures it
+	fn create_layer(&mut self, index: usize) {
+		let qh = self.queue.handle();
+		let compositor = self.state.compositor.as_ref().unwrap();
+		let layer_shell = self.state.layer_shell.as_ref().unwrap();
+		let output = &mut self.state.outputs[index];
+		let surface = compositor.create_surface(&qh, ());
+		let layer_surface = layer_shell.get_layer_surface(&surface, Some(&output.output), zwlr_layer_shell_v1::Layer::Background, "desktop_sim".to_string(), &qh, output.global);
+		// zero size with all four anchors stretches the surface over the whole output
+		layer_surface.set_size(0, 0);
+		layer_surface.set_anchor(zwlr_layer_surface_v1::Anchor::Top | zwlr_layer_surface_v1::Anchor::Bottom | zwlr_layer_surface_v1::Anchor::Left | zwlr_layer_surface_v1::Anchor::Right);
+		layer_surface.set_exclusive_zone(-1);
+		layer_surface.set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::None);
+		surface.commit();
+
+		output.layer = Some(Layer {
+			surface,
+			layer_surface,
+			width: output.w,
+			height: output.h,
+			configured: false,
+			egl_window: std::ptr::null_mut(),
+			egl_surface: None,
+			failed: false
+		});
+	}
+
+	fn create_egl_surface(&mut self, index: usize) -> Result<(), String> {
+		let (display, config) = (self.display.unwrap(), self.config.unwrap());
+		let output = &mut self.state.outputs[index];
+		let layer = output.layer.as_mut().unwrap();
+		layer.egl_window = unsafe { ffi_dispatch!(wayland_egl_handle(), wl_egl_window_create, layer.surface.id().as_ptr(), layer.width, layer.height) };
+		if layer.egl_window.is_null() {
+			return Err(format!("Failed to create EGL window for output {}", output.name));
+		}
+		match unsafe { self.egl.create_window_surface(display, config, layer.egl_window as egl::NativeWindowType, None) } {
+			Ok(egl_surface) => {
+				layer.egl_surface = Some(egl_surface);
+				Ok(())
+			},
+			Err(e) => {
+				unsafe { ffi_dispatch!(wayland_egl_handle(), wl_egl_window_destroy, layer.egl_window) };
+				layer.egl_window = std::ptr::null_mut();
+				Err(format!("Failed to create EGL surface for output {}: {}", output.name, e))
+			}
+		}
+	}
+
+	fn destroy_layer(&self, layer: Layer) {
+		if let (Some(display), Some(egl_surface)) = (self.display, layer.egl_surface) {
+			let _ = self.egl.destroy_surface(display, egl_surface);
+		}
+		if !layer.egl_window.is_null() {
+			unsafe { ffi_dispatch!(wayland_egl_handle(), wl_egl_window_destroy, layer.egl_window) };
+		}
+		layer.layer_surface.destroy();
+		layer.surface.destroy();
+	}
+
+	fn create_layers(&mut self) -> Result<(), String> {
+		for index in 0..self.state.outputs.len() {
+			self.create_layer(index);
+		}
+
+		while self.state.outputs.iter().any(|output| output.layer.as_ref().is_some_and(|layer| !layer.configured)) {
+			self.queue.blocking_dispatch(&mut self.state).map_err(|e| format!("Failed to configure layer surfaces: {}", e))?;
+		}
+		if self.state.outputs.iter().any(|output| output.layer.is_none()) {
+			return Err("Layer surface was closed before it was configured".to_string());
+		}
+		Ok(())
+	}
+
+	fn create_egl(&mut self) -> Result<(), String> {
+		if !is_lib_available() {
+			return Err("Failed to load libwayland-egl".to_string());
+		}
+
+		let display = unsafe { self.egl.get_display(self.connection.backend().display_ptr() as egl::NativeDisplayType) }
+			.ok_or_else(|| "Failed to get EGL display".to_string())?;
+		self.egl.initialize(display).map_err(|e| format!("Failed to initialize EGL: {}", e))?;
+		self.display = Some(display);
+		self.egl.bind_api(egl::OPENGL_API).map_err(|e| format!("Failed to bind OpenGL: {}", e))?;
+
+		let attributes = [
+			egl::SURFACE_TYPE, egl::WINDOW_BIT,
+			egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+			egl::RED_SIZE, 8,
+			egl::GREEN_SIZE, 8,
+			egl::BLUE_SIZE, 8,
+			egl::ALPHA_SIZE, 8,
+			egl::NONE
+		];
+		let config = self.egl.choose_first_config(display, &attributes)
+			.map_err(|e| format!("Failed to choose EGL config: {}", e))?
+			.ok_or_else(|| "No matching EGL config".to_string())?;
+
+		// same 3.3 compatibility context the other backends create, shared by every output
+		let context_attributes = [
+			egl::CONTEXT_MAJOR_VERSION, 3,
+			egl::CONTEXT_MINOR_VERSION, 3,
+			egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT,
+			egl::NONE
+		];
+		let context = self.egl.create_context(display, config, None, &context_attributes).map_err(|e| format!("Failed to create OpenGL context: {}", e))?;
+		self.config = Some(config);
+		self.context = Some(context);
+
+		for index in 0..self.state.outputs.len() {
+			self.create_egl_surface(index)?;
+		}
+
+		let first = self.state.outputs[0].layer.as_ref().unwrap().egl_surface;
+		self.egl.make_current(display, first, first, Some(context)).map_err(|e| format!("Failed to make OpenGL context current: {}", e))?;
+		// presenting one output must not wait for the frame callback of another
+		self.egl.swap_interval(display, 0).map_err(|e| format!("Failed to set swap interval: {}", e))?;
+
+		let egl = &self.egl;
+		gl::load_with(|symbol| egl.get_proc_address(symbol).map_or(std::ptr::null(), |function| function as *const _));
+		println!("GL_VERSION: {}", unsafe { std::ffi::CStr::from_ptr(gl::GetString(gl::VERSION) as *const _).to_string_lossy() });
+		Ok(())
+	}
+
+	// tears down unplugged outputs and sets up new ones as far as the compositor has got with them
+	fn update_outputs(&mut self) {
+		for layer in std::mem::take(&mut self.state.dead_layers) {
+			self.destroy_layer(layer);
+		}
+		if self.context.is_none() {
+			return;
+		}
+		for index in 0..self.state.outputs.len() {
+			let output = &self.state.outputs[index];
+			if !output.done {
+				continue;
+			}
+			match output.layer.as_ref() {
+				None => self.create_layer(index),
+				Some(layer) if layer.configured && layer.egl_surface.is_none() && !layer.failed => {
+					if let Err(e) = self.create_egl_surface(index) {
+						eprintln!("{}, leaving the output blank", e);
+						self.state.outputs[index].layer.as_mut().unwrap().failed = true;
+					}
+				},
+				_ => {}
+			}
+		}
+	}
+}
+
+impl Platform for WaylandPlatform {
+	fn monitors(&mut self) -> Vec<Monitor> {
+		// layer shell clients can't see other clients' exclusive zones, so the work area stays the whole output
+		self.state.outputs.iter().filter(|output| output.done).map(|output| {
+			let mut monitor = Monitor::new(&output.name, output.x, output.y, output.w, output.h);
+			monitor.scale = output.scale as f32;
+			monitor.refresh_rate = output.refresh as f32 / 1000.0;
+			monitor
+		}).collect()
+	}
+
+	fn create_surface(&mut self) -> Result<Surface, String> {
+		if self.state.outputs.is_empty() {
+			return Err("Wayland compositor has no outputs".to_string());
+		}
+		self.create_layers()?;
+		self.create_egl()?;
+		let monitors = self.monitors();
+		self.surface = Surface::bounding(&monitors);
+		self.watcher = MonitorWatcher::new(monitors);
+		Ok(self.surface)
+	}
+
+	fn has_gl(&self) -> bool {
+		true
+	}
+
+	fn begin_output(&mut self, monitor: &Monitor) -> bool {
+		let (display, context) = match (self.display, self.context) {
+			(Some(display), Some(context)) => (display, context),
+			_ => return false
+		};
+		let output = match self.state.outputs.iter().find(|output| output.name == monitor.name) {
+			Some(output) => output,
+			None => return false
+		};
+		// new outputs draw once their surface exists
+		let layer = match output.layer.as_ref() {
+			Some(layer) if layer.egl_surface.is_some() => layer,
+			_ => return false
+		};
+		if self.egl.make_current(display, layer.egl_surface, layer.egl_surface, Some(context)).is_err() {
+			return false;
+		}
+
+		// the renderer places every monitor inside the bounding surface, shift the viewport
+		// so this output's part of it lands on its own layer surface
+		let scale_x = layer.width as f32 / output.w as f32;
+		let scale_y = layer.height as f32 / output.h as f32;
+		let surface = self.surface;
+		unsafe {
+			gl::Viewport(
+				(-(output.x - surface.x) as f32 * scale_x) as i32,
+				(-((surface.y + surface.height) - (output.y + output.h)) as f32 * scale_y) as i32,
+				(surface.width as f32 * scale_x) as i32,
+				(surface.height as f32 * scale_y) as i32);
+			gl::Clear(gl::COLOR_BUFFER_BIT);
+		}
+		true
+	}
+
+	fn present(&mut self) {
+		if let Some(display) = self.display {
+			for output in self.state.outputs.iter() {
+				if let Some(egl_surface) = output.layer.as_ref().and_then(|layer| layer.egl_surface) {
+					let _ = self.egl.swap_buffers(display, egl_surface);
+				}
+			}
+		}
+	}
+
+	fn poll_events(&mut self) -> Vec<Event> {
+		let _ = self.connection.flush();
+		if let Some(guard) = self.queue.prepare_read() {
+			// nothing to read is not an error here, only a dead connection is
+			if let Err(wayland_client::backend::WaylandError::Io(e)) = guard.read() {
+				if e.kind() != std::io::ErrorKind::WouldBlock {
+					self.state.closed = true;
+				}
+			}
+		}
+		if self.queue.dispatch_pending(&mut self.state).is_err() {
+			self.state.closed = true;
+		}
+
+		if self.state.closed {
+			return vec![Event::Quit];
+		}
+
+		self.update_outputs();
+		let monitors = self.monitors();
+		let surface = Surface::bounding(&monitors);
+		let mut events = self.watcher.update(monitors);
+		if surface != self.surface && self.context.is_some() {
+			self.surface = surface;
+			events.push(Event::SurfaceChanged(surface));
+		}
+		events
+	}
+
+	// lock screens and display power aren't visible to ordinary clients
+	fn desktop_state(&mut self) -> DesktopState {
+		DesktopState {
+			obscured: self.state.toplevels.iter().any(|toplevel| toplevel.activated && toplevel.fullscreen),
+			locked: false,
+			on_battery: linux_on_battery(),
+			display_off: false
+		}
+	}
+
+	fn destroy(&mut self) {
+		let display = self.display;
+		if let Some(display) = display {
+			let _ = self.egl.make_current(display, None, None, None);
+		}
+		let mut layers: Vec<Layer> = self.state.outputs.iter_mut().filter_map(|output| output.layer.take()).collect();
+		layers.append(&mut self.state.dead_layers);
+		for layer in layers {
+			self.destroy_layer(layer);
+		}
+		self.display = None;
+		if let Some(display) = display {
+			if let Some(context) = self.context.take() {
+				let _ = self.egl.destroy_context(display, context);
+			}
+			let _ = self.egl.terminate(display);
+		}
+		let _ = self.connection.flush();
+	}
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for State {
+	fn event(state: &mut Self, registry: &wl_registry::WlRegistry, event: wl_registry::Event, _: &(), _: &Connection, qh: &QueueHandle<Self>) {
+		match event {
+			wl_registry::Event::Global { name, interface, version } => match interface.as_str() {
+				"wl_compositor" => state.compositor = Some(registry.bind(name, version.min(4), qh, ())),
+				"zwlr_layer_shell_v1" => state.layer_shell = Some(registry.bind(name, version.min(4), qh, ())),
+				"zwlr_foreign_toplevel_manager_v1" => state.toplevel_manager = Some(registry.bind(name, version.min(3), qh, ())),
+				"wl_output" => {
+					state.outputs.push(Output {
+						output: registry.bind(name, version.min(4), qh, name),
+						global: name,
+						name: format!("wl_output-{}", name),
+						x: 0,
+						y: 0,
+						w: 0,
+						h: 0,
+						scale: 1,
+						refresh: 0,
+						done: false,
+						layer: None
+					});
+				},
+				_ => {}
+			},
+			wl_registry::Event::GlobalRemove { name } => {
+				if let Some(index) = state.outputs.iter().position(|output| output.global == name) {
+					let mut output = state.outputs.remove(index);
+					state.dead_layers.extend(output.layer.take());
+				}
+			},
+			_ => {}
+		}
+	}
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for State {
+	fn event(state: &mut Self, _: &wl_output::WlOutput, event: wl_output::Event, global: &u32, _: &Connection, _: &QueueHandle<Self>) {
+		let output = match state.outputs.iter_mut().find(|output| output.global == *global) {
+			Some(output) => output,
+			None => return
+		};
+		match event {
+			wl_output::Event::Geometry { x, y, .. } => {
+				output.x = x;
+				output.y = y;
+			},
+			wl_output::Event::Mode { flags, width, height, refresh } if flags.into_result().is_ok_and(|flags| flags.contains(wl_output::Mode::Current)) => {
+				output.w = width;
+				output.h = height;
+				output.refresh = refresh;
+			},
+			wl_output::Event::Scale { factor } => output.scale = factor,
+			wl_output::Event::Name { name } => output.name = name,
+			wl_output::Event::Done => output.done = true,
+			_ => {}
+		}
+	}
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, u32> for State {
+	fn event(state: &mut Self, layer_surface: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, event: zwlr_layer_surface_v1::Event, global: &u32, _: &Connection, _: &QueueHandle<Self>) {
+		match event {
+			zwlr_layer_surface_v1::Event::Configure { serial, width, height } => {
+				layer_surface.ack_configure(serial);
+				if let Some(layer) = state.outputs.iter_mut().find(|output| output.global == *global).and_then(|output| output.layer.as_mut()) {
+					if width > 0 && height > 0 {
+						layer.width = width as i32;
+						layer.height = height as i32;
+					}
+					if !layer.egl_window.is_null() {
+						unsafe { ffi_dispatch!(wayland_egl_handle(), wl_egl_window_resize, layer.egl_window, layer.width, layer.height, 0, 0) };
+					}
+					layer.configured = true;
+				}
+			},
+			// the output went away or got disabled, it comes back with its next done event
+			zwlr_layer_surface_v1::Event::Closed => {
+				if let Some(output) = state.outputs.iter_mut().find(|output| output.global == *global) {
+					state.dead_layers.extend(output.layer.take());
+					output.done = false;
+				}
+			},
+			_ => {}
+		}
+	}
+}
+
+impl Dispatch<zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1, ()> for State {
+	fn event(state: &mut Self, _: &zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1, event: zwlr_foreign_toplevel_manager_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+		if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+			state.toplevels.push(Toplevel { handle: toplevel, activated: false, fullscreen: false });
+		}
+	}
+
+	wayland_client::event_created_child!(State, zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1, [
+		zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1, ())
+	]);
+}
+
+impl Dispatch<zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1, ()> for State {
+	fn event(state: &mut Self, handle: &zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1, event: zwlr_foreign_toplevel_handle_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+		match event {
+			zwlr_foreign_toplevel_handle_v1::Event::State { state: states } => {
+				if let Some(toplevel) = state.toplevels.iter_mut().find(|toplevel| &toplevel.handle == handle) {
+					// array of native endian u32 state values
+					let states: Vec<u32> = states.chunks_exact(4).map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect();
+					toplevel.activated = states.contains(&(zwlr_foreign_toplevel_handle_v1::State::Activated as u32));
+					toplevel.fullscreen = states.contains(&(zwlr_foreign_toplevel_handle_v1::State::Fullscreen as u32));
+				}
+			},
+			zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+				state.toplevels.retain(|toplevel| &toplevel.handle != handle);
+				handle.destroy();
+			},
+			_ => {}
+		}
+	}
+}
+
+wayland_client::delegate_noop!(State: ignore wl_compositor::WlCompositor);
+wayland_client::delegate_noop!(State: ignore wl_surface::WlSurface);
+wayland_client::delegate_noop!(State: ignore zwlr_layer_shell_v1::ZwlrLayerShellV1);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[ignore = "needs a Wayland compositor with layer shell"]
+	fn every_output_gets_a_background_surface() {
+		let mut platform = WaylandPlatform::new().unwrap();
+		let monitors = platform.monitors();
+		assert!(!monitors.is_empty());
+		assert!(monitors.iter().all(|monitor| monitor.w > 0 && monitor.h > 0 && monitor.scale > 0.0));
+
+		let surface = platform.create_surface().unwrap();
+		assert_eq!(surface, Surface::bounding(&monitors));
+		assert!(platform.state.outputs.iter().all(|output| output.layer.as_ref().is_some_and(|layer| layer.egl_surface.is_some())));
+		for monitor in monitors.iter() {
+			assert!(platform.begin_output(monitor), "can't draw on {}", monitor.name);
+		}
+
+		// nothing changed, so polling reports nothing either
+		assert!(platform.poll_events().is_empty());
+		assert!(!platform.desktop_state().obscured);
+		platform.destroy();
+	}
+}
@@ -1,6 +1,9 @@
 extern crate winapi;
 extern crate alloc;
 
+use winapi::um::shellscalingapi::{SetProcessDpiAwareness, PROCESS_PER_MONITOR_DPI_AWARE};
+use winapi::um::shellapi::{SHQueryUserNotificationState, QUERY_USER_NOTIFICATION_STATE, QUNS_BUSY, QUNS_RUNNING_D3D_FULL_SCREEN, QUNS_PRESENTATION_MODE};
+use winapi::um::winbase::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
 use winapi::um::winuser::*;
 use winapi::um::wingdi::*;
 use winapi::shared::minwindef::*;
@@ -10,6 +13,10 @@ use alloc::ffi::CString;
 use std::collections::HashMap;
 use std::ptr;
 
+use crate::monitor::*;
+use crate::platform::*;
+use crate::render::Surface;
+
 pub struct WindowInfo {
 	hwnd: HWND,
 	hdc: HDC,
@@ -118,6 +125,141 @@ pub fn window_swap_buffers(window: &WindowInfo) -> () {
 	unsafe { SwapBuffers(window.hdc) };
 }
 
+// seconds between looking for added, removed or resized monitors
+const MONITOR_CHECK_INTERVAL: f32 = 2.0;
+
+pub struct Win32Platform {
+	window: Option<WindowInfo>,
+	watcher: MonitorWatcher,
+	last_monitor_check: std::time::Instant
+}
+
+impl Win32Platform {
+	pub fn new() -> Win32Platform {
+		// monitor rects in physical pixels instead of scaled down ones, fails harmlessly when already set
+		unsafe { SetProcessDpiAwareness(PROCESS_PER_MONITOR_DPI_AWARE) };
+		Win32Platform { window: None, watcher: MonitorWatcher::new(Vec::new()), last_monitor_check: std::time::Instant::now() }
+	}
+}
+
+impl Platform for Win32Platform {
+	fn monitors(&mut self) -> Vec<Monitor> {
+		monitors_list()
+	}
+
+	fn create_surface(&mut self) -> Result<Surface, String> {
+		let window = window_background_create()?;
+		let surface = Surface { x: window.x, y: window.y, width: window.width, height: window.height };
+		self.window = Some(window);
+		self.watcher = MonitorWatcher::new(monitors_list());
+		self.last_monitor_check = std::time::Instant::now();
+		Ok(surface)
+	}
+
+	fn has_gl(&self) -> bool {
+		true
+	}
+
+	fn present(&mut self) {
+		if let Some(window) = self.window.as_ref() {
+			window_swap_buffers(window);
+		}
+	}
+
+	// the WorkerW window belongs to explorer, so there is no WM_DISPLAYCHANGE to wait for, poll instead
+	fn poll_events(&mut self) -> Vec<Event> {
+		let window = match self.window.as_mut() {
+			Some(window) if self.last_monitor_check.elapsed().as_secs_f32() >= MONITOR_CHECK_INTERVAL => window,
+			_ => return Vec::new()
+		};
+		self.last_monitor_check = std::time::Instant::now();
+
+		let mut events = self.watcher.update(monitors_list());
+		if events.is_empty() {
+			return events;
+		}
+
+		// explorer resizes the WorkerW window to the new virtual desktop
+		let mut rect: RECT = RECT { bottom: 0, left: 0, right: 0, top: 0 };
+		if unsafe { GetWindowRect(window.hwnd, &mut rect as *mut RECT) } != 0 {
+			let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+			if width != window.width || height != window.height || rect.left != window.x || rect.top != window.y {
+				window.width = width;
+				window.height = height;
+				window.x = rect.left;
+				window.y = rect.top;
+				unsafe { gl::Viewport(0, 0, width, height) };
+				events.push(Event::SurfaceChanged(Surface { x: window.x, y: window.y, width, height }));
+			}
+		}
+		events
+	}
+
+	// display power needs a window of our own to receive power broadcasts, so it stays false
+	fn desktop_state(&mut self) -> DesktopState {
+		let mut state = DesktopState::default();
+
+		let mut notification_state: QUERY_USER_NOTIFICATION_STATE = 0;
+		if unsafe { SHQueryUserNotificationState(&mut notification_state) } == 0 {
+			state.obscured = notification_state == QUNS_BUSY || notification_state == QUNS_RUNNING_D3D_FULL_SCREEN || notification_state == QUNS_PRESENTATION_MODE;
+		}
+		if !state.obscured {
+			state.obscured = foreground_fullscreen();
+		}
+
+		// the lock screen runs on the Winlogon desktop, which we either can't open or see under its own name
+		let desktop = unsafe { OpenInputDesktop(0, FALSE, DESKTOP_READOBJECTS) };
+		if desktop.is_null() {
+			state.locked = true;
+		} else {
+			let mut name: [u16; 64] = [0; 64];
+			let mut length: DWORD = 0;
+			if unsafe { GetUserObjectInformationW(desktop as _, UOI_NAME as i32, name.as_mut_ptr() as _, std::mem::size_of_val(&name) as DWORD, &mut length) } != 0 {
+				let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+				state.locked = !String::from_utf16_lossy(&name[..end]).eq_ignore_ascii_case("Default");
+			}
+			unsafe { CloseDesktop(desktop) };
+		}
+
+		let mut power_status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+		if unsafe { GetSystemPowerStatus(&mut power_status) } != 0 {
+			state.on_battery = power_status.ACLineStatus == 0;
+		}
+		state
+	}
+
+	fn destroy(&mut self) {
+		if let Some(window) = self.window.take() {
+			window_background_destroy(window);
+		}
+	}
+}
+
+// the foreground window covers its whole monitor, and isn't the desktop itself
+fn foreground_fullscreen() -> bool {
+	let hwnd: HWND = unsafe { GetForegroundWindow() };
+	if hwnd.is_null() || hwnd == unsafe { GetShellWindow() } {
+		return false;
+	}
+
+	let mut class_name: [i8; 64] = [0; 64];
+	let length = unsafe { GetClassNameA(hwnd, class_name.as_mut_ptr(), class_name.len() as i32) };
+	let class_name: Vec<u8> = class_name[..length.max(0) as usize].iter().map(|&c| c as u8).collect();
+	if class_name == b"WorkerW" || class_name == b"Progman" {
+		return false;
+	}
+
+	let mut rect: RECT = RECT { bottom: 0, left: 0, right: 0, top: 0 };
+	let monitor = unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONULL) };
+	let mut monitor_info: MONITORINFO = unsafe { std::mem::zeroed() };
+	monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as DWORD;
+	if monitor.is_null() || unsafe { GetWindowRect(hwnd, &mut rect) } == 0 || unsafe { GetMonitorInfoW(monitor, &mut monitor_info) } == 0 {
+		return false;
+	}
+	let screen = monitor_info.rcMonitor;
+	rect.left <= screen.left && rect.top <= screen.top && rect.right >= screen.right && rect.bottom >= screen.bottom
+}
+
 unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
 	let class_name: CString = CString::new("SHELLDLL_DefView").unwrap();
 	let p: HWND = FindWindowExA(
@@ -0,0 +1,522 @@
+extern crate x11_dl;
+
+use x11_dl::dpms;
+use x11_dl::glx;
+use x11_dl::xlib;
+use x11_dl::xrandr;
+use x11_dl::xss;
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_int, c_long, c_uchar, c_ulong};
+use std::ptr;
+
+use crate::monitor::*;
+use crate::platform::*;
+use crate::render::Surface;
+
+// where to draw, the root window works on bare window managers, desktop
+// environments that paint their own background need the desktop window
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum X11Target {
+	Root,
+	DesktopWindow
+}
+
+type GlXCreateContextAttribsArb = unsafe extern "C" fn(*mut xlib::Display, glx::GLXFBConfig, glx::GLXContext, xlib::Bool, *const c_int) -> glx::GLXContext;
+
+pub struct X11Platform {
+	xlib: xlib::Xlib,
+	xrandr: Option<xrandr::Xrandr>,
+	// screen saver and display power state, optional like XRandR
+	xss: Option<xss::Xss>,
+	xext: Option<dpms::Xext>,
+	glx: glx::Glx,
+	display: *mut xlib::Display,
+	screen: c_int,
+	root: xlib::Window,
+	target: X11Target,
+	window: xlib::Window,
+	colormap: xlib::Colormap,
+	context: glx::GLXContext,
+	wm_delete_window: xlib::Atom,
+	// first XRandR event number, screen changes are only watched when XRandR is there
+	xrandr_event_base: Option<c_int>,
+	surface: Surface,
+	watcher: MonitorWatcher
+}
+
+impl X11Platform {
+	pub fn new(target: X11Target) -> Result<X11Platform, String> {
+		let xlib = xlib::Xlib::open().map_err(|e| format!("Failed to load Xlib: {}", e))?;
+		let glx = glx::Glx::open().map_err(|e| format!("Failed to load GLX: {}", e))?;
+		// monitors fall back to the whole screen without XRandR
+		let xrandr = xrandr::Xrandr::open().ok();
+		let xss = xss::Xss::open().ok();
+		let xext = dpms::Xext::open().ok();
+
+		let display = unsafe { (xlib.XOpenDisplay)(ptr::null()) };
+		if display.is_null() {
+			return Err("Failed to open X display, is DISPLAY set?".to_string());
+		}
+		let screen = unsafe { (xlib.XDefaultScreen)(display) };
+		let root = unsafe { (xlib.XRootWindow)(display, screen) };
+
+		Ok(X11Platform {
+			xlib,
+			xrandr,
+			xss,
+			xext,
+			glx,
+			display,
+			screen,
+			root,
+			target,
+			window: 0,
+			colormap: 0,
+			context: ptr::null_mut(),
+			wm_delete_window: 0,
+			xrandr_event_base: None,
+			surface: Surface { x: 0, y: 0, width: 0, height: 0 },
+			watcher: MonitorWatcher::new(Vec::new())
+		})
+	}
+
+	fn atom(&self, name: &str) -> xlib::Atom {
+		let name = CString::new(name).unwrap();
+		unsafe { (self.xlib.XInternAtom)(self.display, name.as_ptr(), xlib::False) }
+	}
+
+	// 32 bit property values of `window`, empty when the property is missing
+	fn property(&self, window: xlib::Window, property: &str, property_type: xlib::Atom) -> Vec<c_ulong> {
+		let mut actual_type: xlib::Atom = 0;
+		let mut actual_format: c_int = 0;
+		let mut count: c_ulong = 0;
+		let mut bytes_after: c_ulong = 0;
+		let mut data: *mut c_uchar = ptr::null_mut();
+		let status = unsafe {
+			(self.xlib.XGetWindowProperty)(self.display, window, self.atom(property), 0, 1024, xlib::False, property_type,
+				&mut actual_type, &mut actual_format, &mut count, &mut bytes_after, &mut data)
+		};
+		if status != xlib::Success as c_int || data.is_null() {
+			return Vec::new();
+		}
+		// format 32 properties come back as longs
+		let values = if actual_format == 32 {
+			unsafe { std::slice::from_raw_parts(data as *const c_long, count as usize) }.iter().map(|&value| value as c_ulong).collect()
+		} else {
+			Vec::new()
+		};
+		unsafe { (self.xlib.XFree)(data as *mut _) };
+		values
+	}
+
+	// refresh rate of the mode the output's crtc runs, 0 when it has none
+	fn refresh_rate(&self, output: xrandr::RROutput) -> f32 {
+		let xrandr = match self.xrandr.as_ref() {
+			Some(xrandr) => xrandr,
+			None => return 0.0
+		};
+		let resources = unsafe { (xrandr.XRRGetScreenResourcesCurrent)(self.display, self.root) };
+		if resources.is_null() {
+			return 0.0;
+		}
+		let mut refresh_rate = 0.0;
+		let output_info = unsafe { (xrandr.XRRGetOutputInfo)(self.display, resources, output) };
+		if !output_info.is_null() {
+			let crtc_info = unsafe { (xrandr.XRRGetCrtcInfo)(self.display, resources, (*output_info).crtc) };
+			if !crtc_info.is_null() {
+				let modes = unsafe { std::slice::from_raw_parts((*resources).modes, (*resources).nmode.max(0) as usize) };
+				if let Some(mode) = modes.iter().find(|mode| mode.id == unsafe { (*crtc_info).mode }) {
+					if mode.hTotal > 0 && mode.vTotal > 0 {
+						refresh_rate = mode.dotClock as f32 / (mode.hTotal as f32 * mode.vTotal as f32);
+					}
+				}
+				unsafe { (xrandr.XRRFreeCrtcInfo)(crtc_info) };
+			}
+			unsafe { (xrandr.XRRFreeOutputInfo)(output_info) };
+		}
+		unsafe { (xrandr.XRRFreeScreenResources)(resources) };
+		refresh_rate
+	}
+
+	// X has no per monitor scale, desktops put theirs into the Xft.dpi resource
+	fn dpi_scale(&self) -> f32 {
+		let resources = unsafe { (self.xlib.XResourceManagerString)(self.display) };
+		if resources.is_null() {
+			return 1.0;
+		}
+		let resources = unsafe { CStr::from_ptr(resources) }.to_string_lossy();
+		resources.lines()
+			.filter_map(|line| line.strip_prefix("Xft.dpi:"))
+			.find_map(|dpi| dpi.trim().parse::<f32>().ok())
+			.map_or(1.0, |dpi| dpi / 96.0)
+	}
+
+	// the window manager's work area on the current desktop, it spans all monitors
+	fn work_area(&self) -> Option<MonitorRect> {
+		let areas = self.property(self.root, "_NET_WORKAREA", xlib::XA_CARDINAL);
+		let desktop = self.property(self.root, "_NET_CURRENT_DESKTOP", xlib::XA_CARDINAL).first().copied().unwrap_or(0) as usize;
+		let area = areas.chunks_exact(4).nth(desktop).or_else(|| areas.chunks_exact(4).next())?;
+		Some(MonitorRect { x: area[0] as i32, y: area[1] as i32, w: area[2] as i32, h: area[3] as i32 })
+	}
+
+	fn screen_size(&self) -> (i32, i32) {
+		unsafe { ((self.xlib.XDisplayWidth)(self.display, self.screen), (self.xlib.XDisplayHeight)(self.display, self.screen)) }
+	}
+
+	fn choose_fb_config(&self) -> Result<glx::GLXFBConfig, String> {
+		let attributes: [c_int; 17] = [
+			glx::GLX_X_RENDERABLE, xlib::True,
+			glx::GLX_DRAWABLE_TYPE, glx::GLX_WINDOW_BIT,
+			glx::GLX_RENDER_TYPE, glx::GLX_RGBA_BIT,
+			glx::GLX_DOUBLEBUFFER, xlib::True,
+			glx::GLX_RED_SIZE, 8,
+			glx::GLX_GREEN_SIZE, 8,
+			glx::GLX_BLUE_SIZE, 8,
+			glx::GLX_ALPHA_SIZE, 8,
+			0
+		];
+
+		let mut count: c_int = 0;
+		let configs = unsafe { (self.glx.glXChooseFBConfig)(self.display, self.screen, attributes.as_ptr(), &mut count) };
+		if configs.is_null() || count == 0 {
+			return Err("No matching GLX framebuffer config".to_string());
+		}
+		let configs_slice = unsafe { std::slice::from_raw_parts(configs, count as usize) };
+
+		// the root window already has a visual, the config has to match it
+		let mut chosen = Some(configs_slice[0]);
+		if self.target == X11Target::Root {
+			let root_visual = unsafe { (self.xlib.XVisualIDFromVisual)((self.xlib.XDefaultVisual)(self.display, self.screen)) };
+			chosen = configs_slice.iter().copied().find(|&config| {
+				let visual_info = unsafe { (self.glx.glXGetVisualFromFBConfig)(self.display, config) };
+				if visual_info.is_null() {
+					return false;
+				}
+				let visual_id = unsafe { (*visual_info).visualid };
+				unsafe { (self.xlib.XFree)(visual_info as *mut _) };
+				visual_id == root_visual
+			});
+		}
+		unsafe { (self.xlib.XFree)(configs as *mut _) };
+
+		chosen.ok_or_else(|| "No GLX framebuffer config matches the root window visual".to_string())
+	}
+
+	fn create_desktop_window(&mut self, visual_info: *mut xlib::XVisualInfo, width: i32, height: i32) -> Result<xlib::Window, String> {
+		unsafe {
+			self.colormap = (self.xlib.XCreateColormap)(self.display, self.root, (*visual_info).visual, xlib::AllocNone);
+
+			let mut attributes: xlib::XSetWindowAttributes = std::mem::zeroed();
+			attributes.colormap = self.colormap;
+			attributes.border_pixel = 0;
+			attributes.background_pixel = 0;
+			attributes.event_mask = xlib::StructureNotifyMask;
+
+			let window = (self.xlib.XCreateWindow)(
+				self.display,
+				self.root,
+				0,
+				0,
+				width as u32,
+				height as u32,
+				0,
+				(*visual_info).depth,
+				xlib::InputOutput as u32,
+				(*visual_info).visual,
+				xlib::CWColormap | xlib::CWBorderPixel | xlib::CWBackPixel | xlib::CWEventMask,
+				&mut attributes);
+			if window == 0 {
+				return Err("Failed to create desktop window".to_string());
+			}
+
+			let title = CString::new("desktop_sim").unwrap();
+			(self.xlib.XStoreName)(self.display, window, title.as_ptr());
+
+			// ask the window manager to keep us below everything, on every workspace, out of the taskbar
+			let window_type = [self.atom("_NET_WM_WINDOW_TYPE_DESKTOP")];
+			(self.xlib.XChangeProperty)(self.display, window, self.atom("_NET_WM_WINDOW_TYPE"), xlib::XA_ATOM, 32, xlib::PropModeReplace, window_type.as_ptr() as *const c_uchar, 1);
+			let state = [self.atom("_NET_WM_STATE_BELOW"), self.atom("_NET_WM_STATE_STICKY"), self.atom("_NET_WM_STATE_SKIP_TASKBAR"), self.atom("_NET_WM_STATE_SKIP_PAGER")];
+			(self.xlib.XChangeProperty)(self.display, window, self.atom("_NET_WM_STATE"), xlib::XA_ATOM, 32, xlib::PropModeReplace, state.as_ptr() as *const c_uchar, state.len() as c_int);
+
+			self.wm_delete_window = self.atom("WM_DELETE_WINDOW");
+			let mut protocols = [self.wm_delete_window];
+			(self.xlib.XSetWMProtocols)(self.display, window, protocols.as_mut_ptr(), 1);
+
+			(self.xlib.XMapWindow)(self.display, window);
+			(self.xlib.XLowerWindow)(self.display, window);
+			(self.xlib.XSync)(self.display, xlib::False);
+
+			Ok(window)
+		}
+	}
+
+	fn create_context(&self, config: glx::GLXFBConfig, visual_info: *mut xlib::XVisualInfo) -> Result<glx::GLXContext, String> {
+		// a 3.3 compatibility context like the one WGL hands out, so the shaders work without VAOs
+		let name = CString::new("glXCreateContextAttribsARB").unwrap();
+		let create_context_attribs = unsafe { (self.glx.glXGetProcAddressARB)(name.as_ptr() as *const c_uchar) };
+		let context = match create_context_attribs {
+			Some(function) => {
+				let function: GlXCreateContextAttribsArb = unsafe { std::mem::transmute(function) };
+				let attributes: [c_int; 7] = [
+					glx::arb::GLX_CONTEXT_MAJOR_VERSION_ARB, 3,
+					glx::arb::GLX_CONTEXT_MINOR_VERSION_ARB, 3,
+					glx::arb::GLX_CONTEXT_PROFILE_MASK_ARB, glx::arb::GLX_CONTEXT_COMPATIBILITY_PROFILE_BIT_ARB,
+					0
+				];
+				unsafe { function(self.display, config, ptr::null_mut(), xlib::True, attributes.as_ptr()) }
+			},
+			None => unsafe { (self.glx.glXCreateContext)(self.display, visual_info, ptr::null_mut(), xlib::True) }
+		};
+
+		if context.is_null() {
+			return Err("Failed to create OpenGL context".to_string());
+		}
+		Ok(context)
+	}
+}
+
+impl Platform for X11Platform {
+	fn monitors(&mut self) -> Vec<Monitor> {
+		let mut monitors = Vec::<Monitor>::new();
+		if let Some(xrandr) = self.xrandr.as_ref() {
+			let mut count: c_int = 0;
+			let infos = unsafe { (xrandr.XRRGetMonitors)(self.display, self.root, xlib::True, &mut count) };
+			if !infos.is_null() {
+				for info in unsafe { std::slice::from_raw_parts(infos, count.max(0) as usize) } {
+					let name_ptr = unsafe { (self.xlib.XGetAtomName)(self.display, info.name) };
+					let name = if name_ptr.is_null() {
+						String::new()
+					} else {
+						let name = unsafe { CStr::from_ptr(name_ptr) }.to_string_lossy().into_owned();
+						unsafe { (self.xlib.XFree)(name_ptr as *mut _) };
+						name
+					};
+					let mut monitor = Monitor::new(&name, info.x, info.y, info.width, info.height);
+					monitor.primary = info.primary != 0;
+					if info.noutput > 0 && !info.outputs.is_null() {
+						monitor.refresh_rate = self.refresh_rate(unsafe { *info.outputs });
+					}
+					monitors.push(monitor);
+				}
+				unsafe { (xrandr.XRRFreeMonitors)(infos) };
+			}
+		}
+
+		if monitors.is_empty() {
+			let (width, height) = self.screen_size();
+			let mut monitor = Monitor::new("screen", 0, 0, width, height);
+			monitor.primary = true;
+			monitors.push(monitor);
+		}
+
+		let scale = self.dpi_scale();
+		let work_area = self.work_area();
+		for monitor in monitors.iter_mut() {
+			monitor.scale = scale;
+			if let Some(area) = work_area {
+				let left = monitor.x.max(area.x);
+				let top = monitor.y.max(area.y);
+				let right = (monitor.x + monitor.w).min(area.x + area.w);
+				let bottom = (monitor.y + monitor.h).min(area.y + area.h);
+				if right > left && bottom > top {
+					monitor.work = MonitorRect { x: left, y: top, w: right - left, h: bottom - top };
+				}
+			}
+		}
+		monitors
+	}
+
+	fn create_surface(&mut self) -> Result<Surface, String> {
+		let (width, height) = self.screen_size();
+		let config = self.choose_fb_config()?;
+		let visual_info = unsafe { (self.glx.glXGetVisualFromFBConfig)(self.display, config) };
+		if visual_info.is_null() {
+			return Err("GLX framebuffer config has no visual".to_string());
+		}
+
+		let result = (|| {
+			self.window = match self.target {
+				X11Target::Root => self.root,
+				X11Target::DesktopWindow => self.create_desktop_window(visual_info, width, height)?
+			};
+			self.context = self.create_context(config, visual_info)?;
+			if unsafe { (self.glx.glXMakeCurrent)(self.display, self.window, self.context) } == 0 {
+				return Err("Failed to make OpenGL context current".to_string());
+			}
+			Ok(())
+		})();
+		unsafe { (self.xlib.XFree)(visual_info as *mut _) };
+		result?;
+
+		let glx = &self.glx;
+		gl::load_with(|symbol| {
+			let symbol = CString::new(symbol).unwrap();
+			match unsafe { (glx.glXGetProcAddress)(symbol.as_ptr() as *const c_uchar) } {
+				Some(function) => function as *const _,
+				None => ptr::null()
+			}
+		});
+		println!("GL_VERSION: {}", unsafe { CStr::from_ptr(gl::GetString(gl::VERSION) as *const _).to_string_lossy() });
+
+		if let Some(xrandr) = self.xrandr.as_ref() {
+			let mut event_base: c_int = 0;
+			let mut error_base: c_int = 0;
+			if unsafe { (xrandr.XRRQueryExtension)(self.display, &mut event_base, &mut error_base) } != 0 {
+				unsafe { (xrandr.XRRSelectInput)(self.display, self.root, xrandr::RRScreenChangeNotifyMask) };
+				self.xrandr_event_base = Some(event_base);
+			}
+		}
+		self.watcher = MonitorWatcher::new(self.monitors());
+		self.surface = Surface { x: 0, y: 0, width, height };
+		Ok(self.surface)
+	}
+
+	fn has_gl(&self) -> bool {
+		true
+	}
+
+	fn present(&mut self) {
+		if self.window != 0 {
+			unsafe { (self.glx.glXSwapBuffers)(self.display, self.window) };
+		}
+	}
+
+	fn poll_events(&mut self) -> Vec<Event> {
+		let mut events = Vec::new();
+		let mut screen_changed = false;
+		while unsafe { (self.xlib.XPending)(self.display) } > 0 {
+			let mut event: xlib::XEvent = unsafe { std::mem::zeroed() };
+			unsafe { (self.xlib.XNextEvent)(self.display, &mut event) };
+			match event.get_type() {
+				xlib::ClientMessage => {
+					let message: xlib::XClientMessageEvent = From::from(event);
+					if message.data.get_long(0) as xlib::Atom == self.wm_delete_window {
+						events.push(Event::Quit);
+					}
+				},
+				xlib::DestroyNotify => {
+					let destroyed: xlib::XDestroyWindowEvent = From::from(event);
+					if destroyed.window == self.window {
+						events.push(Event::Quit);
+					}
+				},
+				event_type if Some(event_type) == self.xrandr_event_base.map(|base| base + xrandr::RRScreenChangeNotify) => {
+					// keeps XDisplayWidth and XDisplayHeight up to date
+					if let Some(xrandr) = self.xrandr.as_ref() {
+						unsafe { (xrandr.XRRUpdateConfiguration)(&mut event) };
+					}
+					screen_changed = true;
+				},
+				_ => {}
+			}
+		}
+
+		if screen_changed {
+			let monitors = self.monitors();
+			events.extend(self.watcher.update(monitors));
+
+			let (width, height) = self.screen_size();
+			if width != self.surface.width || height != self.surface.height {
+				if self.target == X11Target::DesktopWindow {
+					unsafe { (self.xlib.XResizeWindow)(self.display, self.window, width as u32, height as u32) };
+				}
+				unsafe { gl::Viewport(0, 0, width, height) };
+				self.surface = Surface { x: 0, y: 0, width, height };
+				events.push(Event::SurfaceChanged(self.surface));
+			}
+		}
+		events
+	}
+
+	fn desktop_state(&mut self) -> DesktopState {
+		let mut state = DesktopState::default();
+
+		let active = self.property(self.root, "_NET_ACTIVE_WINDOW", xlib::XA_WINDOW).first().copied().unwrap_or(0);
+		if active != 0 && active != self.window {
+			let fullscreen = self.atom("_NET_WM_STATE_FULLSCREEN");
+			state.obscured = self.property(active, "_NET_WM_STATE", xlib::XA_ATOM).contains(&fullscreen);
+		}
+
+		// lockers run through the screen saver extension
+		if let Some(xss) = self.xss.as_ref() {
+			unsafe {
+				let info = (xss.XScreenSaverAllocInfo)();
+				if !info.is_null() {
+					if (xss.XScreenSaverQueryInfo)(self.display, self.root, info) != 0 {
+						state.locked = (*info).state == xss::ScreenSaverOn;
+					}
+					(self.xlib.XFree)(info as *mut _);
+				}
+			}
+		}
+
+		if let Some(xext) = self.xext.as_ref() {
+			let (mut event_base, mut error_base) = (0, 0);
+			if unsafe { (xext.DPMSQueryExtension)(self.display, &mut event_base, &mut error_base) } != 0 {
+				let mut level = dpms::DPMSModeOn;
+				let mut enabled = 0;
+				if unsafe { (xext.DPMSInfo)(self.display, &mut level, &mut enabled) } != 0 {
+					state.display_off = enabled != 0 && level != dpms::DPMSModeOn;
+				}
+			}
+		}
+
+		state.on_battery = linux_on_battery();
+		state
+	}
+
+	fn destroy(&mut self) {
+		unsafe {
+			if !self.context.is_null() {
+				(self.glx.glXMakeCurrent)(self.display, 0, ptr::null_mut());
+				(self.glx.glXDestroyContext)(self.display, self.context);
+				self.context = ptr::null_mut();
+			}
+			if self.window != 0 && self.window != self.root {
+				(self.xlib.XDestroyWindow)(self.display, self.window);
+			}
+			self.window = 0;
+			if self.colormap != 0 {
+				(self.xlib.XFreeColormap)(self.display, self.colormap);
+				self.colormap = 0;
+			}
+			if !self.display.is_null() {
+				(self.xlib.XCloseDisplay)(self.display);
+				self.display = ptr::null_mut();
+			}
+		}
+	}
+}
+
+
+// needs an X server, see the README for running these under Xvfb
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[ignore = "needs an X server"]
+	fn monitors_cover_the_screen() {
+		let mut platform = X11Platform::new(X11Target::Root).unwrap();
+		let monitors = platform.monitors();
+		let (width, height) = platform.screen_size();
+		assert!(!monitors.is_empty());
+		assert!(monitors.iter().all(|monitor| monitor.w > 0 && monitor.h > 0 && monitor.scale > 0.0));
+		assert!(monitors.iter().any(|monitor| monitor.primary), "no primary monitor");
+		assert_eq!(Surface::bounding(&monitors), Surface { x: 0, y: 0, width, height });
+
+		// nothing changed, so polling reports nothing either
+		let mut watcher = MonitorWatcher::new(monitors);
+		assert!(watcher.update(platform.monitors()).is_empty());
+		platform.destroy();
+	}
+
+	#[test]
+	#[ignore = "needs an X server"]
+	fn an_idle_desktop_is_not_obscured() {
+		let mut platform = X11Platform::new(X11Target::Root).unwrap();
+		assert!(!platform.desktop_state().obscured);
+		platform.destroy();
+	}
+}